@@ -1,83 +1,220 @@
-use std::time::Duration;
+use std::collections::{HashMap, HashSet};
 
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
 
 use crate::{
     enemy::Enemy,
-    metronome::{Metronome, nanos_per_beat},
+    health::{DamageEvent, DamageKind},
+    metronome::{Metronome, MetronomeTimer, steps_per_beat_unit},
     player::Player,
+    score::{Dynamic, Element},
+    shared_meshes::SharedMeshes,
+    status_effect::{ApplyStatusEffect, StatusEffectKind},
 };
 
 #[derive(Component, Debug)]
 pub struct Aoe {
     initial_radius: f32,
     final_radius: f32,
-    for_num_beats: u8,
-    timer: Timer,
+    knockback: f32,
+    damage_per_pulse: u128,
+    falloff: f32,
+    pulse_effect: Option<StatusEffectKind>,
+    steps_per_pulse: u8,
+    timer: MetronomeTimer,
+    entities_damaged_on_pulse: HashMap<u8, HashSet<Entity>>,
+    element: Option<Element>,
 }
 
+/// How many beats enemies caught in a ring are pushed back for
+const KNOCKBACK_BEATS: u8 = 3;
+
+/// How much harder water rings push
+const WATER_KNOCKBACK_MULTIPLIER: f32 = 1.5;
+/// Damage a water push deals for every unit an enemy is pushed
+const WATER_DAMAGE_PER_DISTANCE: f32 = 0.02;
+
 #[derive(Bundle)]
 pub struct AoeBundle {
     aoe: Aoe,
     transform: Transform,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AoeStats {
+    pub initial_radius: f32,
+    pub final_radius: f32,
+    /// Speed enemies caught in the ring are pushed away at
+    pub knockback: f32,
+    pub damage_per_pulse: u128,
+    /// Share of the pulse damage lost by enemies at the edge of the ring
+    pub falloff: f32,
+    /// Applied to enemies caught in a pulse until the next one
+    pub pulse_effect: Option<StatusEffectKind>,
+}
+
+/// Louder rings reach further, push harder and pulse harder, and the loudest slow or stun
+pub const fn aoe_stats(dynamic: Dynamic) -> AoeStats {
+    let (initial_radius, final_radius, knockback, damage_per_pulse) = match dynamic {
+        Dynamic::Ppp => (15., 40., 30., 1),
+        Dynamic::Pp => (20., 50., 40., 1),
+        Dynamic::P => (25., 60., 50., 1),
+        Dynamic::Mp => (28., 68., 55., 1),
+        Dynamic::Mf => (30., 75., 60., 1),
+        Dynamic::F => (35., 85., 70., 2),
+        Dynamic::Ff => (40., 95., 80., 2),
+        Dynamic::Fff => (45., 110., 90., 3),
+    };
+    AoeStats {
+        initial_radius,
+        final_radius,
+        knockback,
+        damage_per_pulse,
+        falloff: 0.5,
+        pulse_effect: match dynamic {
+            Dynamic::Ff => Some(StatusEffectKind::Slow),
+            Dynamic::Fff => Some(StatusEffectKind::Stun),
+            _ => None,
+        },
+    }
+}
+
+/// A ring lasting `for_num_beats` steps that pulses for damage every `steps_per_pulse`
 pub fn aoe_bundle(
-    metronome: &Metronome,
-    initial_radius: f32,
-    final_radius: f32,
+    AoeStats {
+        initial_radius,
+        final_radius,
+        knockback,
+        damage_per_pulse,
+        falloff,
+        pulse_effect,
+    }: AoeStats,
     for_num_beats: u8,
+    steps_per_pulse: u8,
+    element: Option<Element>,
 ) -> AoeBundle {
     AoeBundle {
         aoe: Aoe {
             initial_radius,
             final_radius,
-            for_num_beats,
-            #[allow(clippy::cast_precision_loss)]
-            timer: Timer::new(
-                Duration::from_nanos(nanos_per_beat(metronome.bpm) * u64::from(for_num_beats)),
-                TimerMode::Repeating,
-            ),
+            knockback,
+            damage_per_pulse,
+            falloff,
+            pulse_effect,
+            steps_per_pulse: steps_per_pulse.max(1),
+            timer: MetronomeTimer::new(for_num_beats),
+            entities_damaged_on_pulse: HashMap::new(),
+            element,
         },
-        transform: Transform::from_xyz(0., 0., 2.),
+        transform: Transform::from_xyz(0., 0., 2.).with_scale(Vec3::new(
+            initial_radius,
+            initial_radius,
+            1.,
+        )),
     }
 }
 
+/// Gives a new ring the shared unit circle and a unit collider, both sized through its `Transform`
+#[allow(clippy::needless_pass_by_value)]
+pub fn on_aoe_add(
+    event: On<Add, Aoe>,
+    mut commands: Commands,
+    shared_meshes: Res<SharedMeshes>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    aoe_query: Query<&Aoe>,
+) {
+    let color = aoe_query
+        .get(event.entity)
+        .ok()
+        .and_then(|aoe| aoe.element)
+        .map_or(Color::hsva(0., 0., 1., 1.), Element::color);
+    commands.entity(event.entity).try_insert((
+        Mesh2d(shared_meshes.circle.clone()),
+        MeshMaterial2d(materials.add(color.with_alpha(0.1))),
+        Collider::ball(1.),
+        CollisionGroups::new(Group::GROUP_2, Group::ALL),
+        Sensor,
+        ActiveEvents::COLLISION_EVENTS,
+    ));
+}
+
 #[allow(clippy::needless_pass_by_value)]
 pub fn aoe_system(
     metronome: Res<Metronome>,
-    time: Res<Time>,
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    mut aoe_query: Query<(Entity, &mut Aoe, &Transform)>,
+    mut aoe_query: Query<(Entity, &mut Aoe, &mut Transform)>,
 ) {
-    for (entity, mut aoe, _) in &mut aoe_query {
-        commands.entity(entity).try_insert_if_new((
-            Mesh2d(meshes.add(Circle::new(aoe.initial_radius))),
-            MeshMaterial2d(materials.add(Color::hsva(0., 0., 1., 0.1))),
-            Collider::ball(aoe.initial_radius),
-            CollisionGroups::new(Group::GROUP_2, Group::ALL),
-            Sensor,
-            ActiveEvents::COLLISION_EVENTS,
-        ));
-        aoe.timer.tick(time.delta());
-        if aoe.timer.just_finished() {
+    for (entity, mut aoe, mut transform) in &mut aoe_query {
+        aoe.timer.tick(&metronome);
+        if aoe.timer.just_finished(&metronome) {
             commands.entity(entity).try_despawn();
         } else {
             let radius_diff = aoe.final_radius - aoe.initial_radius;
-            #[allow(clippy::cast_precision_loss)]
-            let total_nanos = nanos_per_beat(metronome.bpm) * u64::from(aoe.for_num_beats);
-            let nanos_so_far = aoe.timer.elapsed().as_nanos();
-            #[allow(clippy::cast_precision_loss)]
-            let progress = nanos_so_far as f32 / total_nanos as f32;
-            let radius = radius_diff.mul_add(progress, aoe.initial_radius);
-
-            commands.entity(entity).try_insert((
-                Mesh2d(meshes.add(Circle::new(radius as f32))),
-                Collider::ball(radius),
-            ));
+            let radius = radius_diff.mul_add(aoe.timer.fraction(&metronome), aoe.initial_radius);
+
+            transform.scale = Vec3::new(radius, radius, 1.);
+        }
+    }
+}
+
+/// Damages every enemy inside the ring once per pulse, less towards the edge, and applies
+/// the ring's status effect
+#[allow(clippy::needless_pass_by_value)]
+pub fn aoe_pulse_system(
+    rapier_context: ReadRapierContext,
+    mut damage_events: MessageWriter<DamageEvent>,
+    mut apply_status_effects: MessageWriter<ApplyStatusEffect>,
+    mut aoe_query: Query<(Entity, &mut Aoe, &GlobalTransform)>,
+    enemy_query: Query<(Entity, &Transform), With<Enemy>>,
+) {
+    let Ok(rapier_context) = rapier_context.single() else {
+        return;
+    };
+    for (aoe_entity, mut aoe, aoe_transform) in &mut aoe_query {
+        if aoe.timer.finished() {
+            continue;
+        }
+        let pulse = aoe.timer.beats_elapsed() / aoe.steps_per_pulse;
+        let radius = aoe_transform.scale().x;
+        let damage_per_pulse = aoe.damage_per_pulse;
+        let falloff = aoe.falloff;
+        let pulse_effect = aoe.pulse_effect;
+        let steps_per_pulse = aoe.steps_per_pulse;
+        let entities_damaged = aoe
+            .entities_damaged_on_pulse
+            .entry(pulse)
+            .or_insert_with(HashSet::new);
+        for (enemy_entity, enemy_transform) in enemy_query {
+            if rapier_context.intersection_pair(aoe_entity, enemy_entity) == Some(true)
+                && entities_damaged.insert(enemy_entity)
+            {
+                let distance = enemy_transform
+                    .translation
+                    .xy()
+                    .distance(aoe_transform.translation().xy());
+                let scale = falloff.mul_add(-(distance / radius).min(1.), 1.);
+                #[allow(
+                    clippy::cast_possible_truncation,
+                    clippy::cast_precision_loss,
+                    clippy::cast_sign_loss
+                )]
+                let amount = ((damage_per_pulse as f32 * scale).round() as u128).max(1);
+                damage_events.write(DamageEvent {
+                    source: aoe_entity,
+                    target: enemy_entity,
+                    amount,
+                    kind: DamageKind::Aoe,
+                });
+                if let Some(kind) = pulse_effect {
+                    apply_status_effects.write(ApplyStatusEffect {
+                        source: aoe_entity,
+                        target: enemy_entity,
+                        kind,
+                        beats: steps_per_pulse,
+                    });
+                }
+            }
         }
     }
 }
@@ -85,21 +222,39 @@ pub fn aoe_system(
 #[derive(Component)]
 pub struct AoeDuration {
     pub velocity: f32,
-    pub timer: Timer,
+    pub timer: MetronomeTimer,
+    /// Where a water push started and the ring that pushed, to deal damage once it ends
+    pub water_push: Option<(Vec2, Entity)>,
 }
 
 #[allow(clippy::needless_pass_by_value)]
 pub fn process_aoe_duration(
-    time: Res<Time>,
+    metronome: Res<Metronome>,
     mut commands: Commands,
+    mut damage_events: MessageWriter<DamageEvent>,
     mut query: Query<(Entity, &mut AoeDuration, &Transform, &mut Velocity)>,
     mut player_query: Query<&Transform, With<Player>>,
 ) {
     for (entity, mut aoe_duration, transform, mut velocity) in &mut query {
-        aoe_duration.timer.tick(time.delta());
-        if aoe_duration.timer.just_finished() {
+        aoe_duration.timer.tick(&metronome);
+        if aoe_duration.timer.just_finished(&metronome) {
             commands.entity(entity).try_remove::<AoeDuration>();
             velocity.linvel = Vec2::ZERO;
+            if let Some((pushed_from, aoe_entity)) = aoe_duration.water_push {
+                let distance = transform.translation.xy().distance(pushed_from);
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                let amount = (distance * WATER_DAMAGE_PER_DISTANCE).round() as u128;
+                if amount > 0 {
+                    damage_events.write(DamageEvent {
+                        source: aoe_entity,
+                        target: entity,
+                        amount,
+                        kind: DamageKind::Aoe,
+                    });
+                }
+            }
+        } else if !metronome.started {
+            velocity.linvel = Vec2::ZERO;
         } else if let Ok(player_transform) = player_query.single_mut() {
             let direction = (transform.translation.xy() - player_transform.translation.xy())
                 .normalize_or_zero();
@@ -114,27 +269,53 @@ pub fn aoe_collision_system(
     mut commands: Commands,
     mut collision_events: MessageReader<CollisionEvent>,
     query_aoe: Query<(Entity, &Aoe, &Transform)>,
-    query_enemy: Query<(Entity, &Enemy, &Transform)>,
+    query_enemy: Query<(&Transform, Option<&AoeDuration>), With<Enemy>>,
 ) {
-    let velocity = 60.;
     for collision_event in collision_events.read() {
         if let CollisionEvent::Started(entity1, entity2, _) = collision_event {
-            let enemy_entity =
-                if query_aoe.get(*entity1).is_ok() && query_enemy.get(*entity2).is_ok() {
-                    *entity2
-                } else if query_aoe.get(*entity2).is_ok() && query_enemy.get(*entity1).is_ok() {
-                    *entity1
+            let (aoe_entity, aoe, enemy_entity, (enemy_transform, aoe_duration)) =
+                if let Ok((aoe_entity, aoe, _)) = query_aoe.get(*entity1)
+                    && let Ok(enemy) = query_enemy.get(*entity2)
+                {
+                    (aoe_entity, aoe, *entity2, enemy)
+                } else if let Ok((aoe_entity, aoe, _)) = query_aoe.get(*entity2)
+                    && let Ok(enemy) = query_enemy.get(*entity1)
+                {
+                    (aoe_entity, aoe, *entity1, enemy)
                 } else {
                     continue;
                 };
 
+            let is_water = aoe.element == Some(Element::Water);
             commands.entity(enemy_entity).try_insert(AoeDuration {
-                velocity,
-                timer: Timer::new(
-                    Duration::from_nanos(nanos_per_beat(metronome.bpm)) * 12,
-                    TimerMode::Once,
-                ),
+                velocity: if is_water {
+                    aoe.knockback * WATER_KNOCKBACK_MULTIPLIER
+                } else {
+                    aoe.knockback
+                },
+                timer: MetronomeTimer::new(KNOCKBACK_BEATS * steps_per_beat_unit(&metronome)),
+                // A push already under way keeps hurting from where it started
+                water_push: aoe_duration
+                    .and_then(|aoe_duration| aoe_duration.water_push)
+                    .or_else(|| is_water.then(|| (enemy_transform.translation.xy(), aoe_entity))),
             });
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fortissimo_rings_outdo_pianissimo_ones() {
+        let [pp, ff] = [Dynamic::Pp, Dynamic::Ff]
+            .map(|dynamic| aoe_bundle(aoe_stats(dynamic), 8, 4, None).aoe);
+        assert!(pp.initial_radius < ff.initial_radius);
+        assert!(pp.final_radius < ff.final_radius);
+        assert!(pp.knockback < ff.knockback);
+        assert!(pp.damage_per_pulse < ff.damage_per_pulse);
+        assert_eq!(pp.pulse_effect, None);
+        assert_eq!(ff.pulse_effect, Some(StatusEffectKind::Slow));
+    }
+}
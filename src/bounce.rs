@@ -1,7 +1,7 @@
 use bevy::prelude::*;
 use bevy_ecs_tilemap::tiles::TileTextureIndex;
 
-use crate::metronome::Metronome;
+use crate::metronome::{BeatCrossed, Metronome, down_beats};
 
 #[derive(Component)]
 pub struct Bounce {
@@ -16,27 +16,35 @@ pub const fn initial_bounce(scale: f32) -> Bounce {
     }
 }
 
+/// Bounce on every other down beat, or on every down beat in short measures
+fn bounce_beats(metronome: &Metronome) -> Vec<u8> {
+    let down_beats = down_beats(metronome);
+    if down_beats.len() < 4 {
+        down_beats
+    } else {
+        down_beats.into_iter().step_by(2).collect()
+    }
+}
+
 #[allow(clippy::needless_pass_by_value)]
 pub fn bounce_system(
     metronome: Res<Metronome>,
+    mut beat_crossed: MessageReader<BeatCrossed>,
     mut bouncers: Query<(&mut Transform, &mut Bounce)>,
 ) {
     if metronome.started {
+        let bounce_beats = bounce_beats(&metronome);
+        let beats: Vec<_> = beat_crossed
+            .read()
+            .map(|beat_crossed| beat_crossed.beat)
+            .collect();
         for (mut transform, mut bounce) in &mut bouncers {
             let initial_scale = *bounce.initial_scale.get_or_insert(transform.scale);
 
-            if metronome.is_beat_start_frame {
-                if metronome.beat == 0 {
-                    transform.scale *= bounce.scale;
-                }
-                if metronome.beat == 1 {
-                    transform.scale = initial_scale;
-                }
-
-                if metronome.beat == 8 {
-                    transform.scale *= bounce.scale;
-                }
-                if metronome.beat == 9 {
+            for beat in &beats {
+                if bounce_beats.contains(beat) {
+                    transform.scale = initial_scale * bounce.scale;
+                } else if bounce_beats.contains(&beat.wrapping_sub(1)) {
                     transform.scale = initial_scale;
                 }
             }
@@ -60,26 +68,24 @@ pub const fn initial_tile_bounce(texture_index: TileTextureIndex) -> TileBounce
 #[allow(clippy::needless_pass_by_value)]
 pub fn tile_bounce_system(
     metronome: Res<Metronome>,
+    mut beat_crossed: MessageReader<BeatCrossed>,
     mut bouncers: Query<(&mut TileBounce, &mut TileTextureIndex)>,
 ) {
     if metronome.started {
+        let bounce_beats = bounce_beats(&metronome);
+        let beats: Vec<_> = beat_crossed
+            .read()
+            .map(|beat_crossed| beat_crossed.beat)
+            .collect();
         for (mut bounce, mut tile_texture_index) in &mut bouncers {
             let initial_texture_index = *bounce
                 .initial_texture_index
                 .get_or_insert(*tile_texture_index);
 
-            if metronome.is_beat_start_frame {
-                if metronome.beat == 0 {
+            for beat in &beats {
+                if bounce_beats.contains(beat) {
                     *tile_texture_index = bounce.texture_index;
-                }
-                if metronome.beat == 1 {
-                    *tile_texture_index = initial_texture_index;
-                }
-
-                if metronome.beat == 8 {
-                    *tile_texture_index = bounce.texture_index;
-                }
-                if metronome.beat == 9 {
+                } else if bounce_beats.contains(&beat.wrapping_sub(1)) {
                     *tile_texture_index = initial_texture_index;
                 }
             }
@@ -3,19 +3,29 @@ use bevy_rapier2d::prelude::*;
 
 use crate::{
     enemy::Enemy,
-    health::Health,
+    game_state::InRun,
+    health::{DamageEvent, DamageKind},
     map::BlocksProjectiles,
     metronome::{Metronome, MetronomeTimer},
     nearest_entity::find_nearest_entity,
+    score::{Dynamic, Element},
+    shared_meshes::SharedMeshes,
+    status_effect::{ApplyStatusEffect, StatusEffectKind},
 };
 
+const FIRE_EXPLOSION_RADIUS: f32 = 40.;
+/// Steps enemies caught in a fire explosion keep burning for
+const BURN_STEPS: u8 = 8;
+
 #[derive(Component)]
 pub struct BulletLauncher {
     radius: f32,
     velocity: f32,
     damage: u128,
+    bullets_per_beat: u8,
     timer: MetronomeTimer,
     last_fired_on_beat: Option<u8>,
+    element: Option<Element>,
 }
 
 #[derive(Component)]
@@ -23,6 +33,13 @@ pub struct Bullet {
     velocity: f32,
     damage: u128,
     target: Option<Entity>,
+    element: Option<Element>,
+}
+
+/// The flash left behind by a fire bullet exploding
+#[derive(Component)]
+pub struct Explosion {
+    timer: Timer,
 }
 
 #[derive(Resource)]
@@ -43,11 +60,36 @@ pub struct BulletLauncherBundle {
     transform: Transform,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BulletStats {
+    pub damage: u128,
+    pub bullets_per_beat: u8,
+}
+
+/// Louder launchers fire more and harder hitting bullets
+pub const fn bullet_stats(dynamic: Dynamic) -> BulletStats {
+    let (damage, bullets_per_beat) = match dynamic {
+        Dynamic::Ppp | Dynamic::Pp => (1, 1),
+        Dynamic::P | Dynamic::Mp | Dynamic::Mf => (2, 1),
+        Dynamic::F => (3, 1),
+        Dynamic::Ff => (3, 2),
+        Dynamic::Fff => (4, 2),
+    };
+    BulletStats {
+        damage,
+        bullets_per_beat,
+    }
+}
+
 pub const fn bullet_launcher_bundle(
     radius: f32,
     velocity: f32,
-    damage: u128,
+    BulletStats {
+        damage,
+        bullets_per_beat,
+    }: BulletStats,
     number_beats_duration: u8,
+    element: Option<Element>,
 ) -> BulletLauncherBundle {
     BulletLauncherBundle {
         bullet_launcher: BulletLauncher {
@@ -55,7 +97,9 @@ pub const fn bullet_launcher_bundle(
             velocity,
             timer: MetronomeTimer::new(number_beats_duration),
             damage,
+            bullets_per_beat,
             last_fired_on_beat: None,
+            element,
         },
         transform: Transform::from_xyz(0., 0., 2.),
     }
@@ -65,7 +109,7 @@ pub const fn bullet_launcher_bundle(
 #[allow(clippy::needless_pass_by_value)]
 pub fn bullet_launcher_system(
     bullet_sfx: Res<BulletSFX>,
-    mut meshes: ResMut<Assets<Mesh>>,
+    shared_meshes: Res<SharedMeshes>,
     mut materials: ResMut<Assets<ColorMaterial>>,
     mut commands: Commands,
     metronome: Res<Metronome>,
@@ -77,38 +121,69 @@ pub fn bullet_launcher_system(
             bullet_launcher.timer.tick(&metronome);
             if bullet_launcher.timer.just_finished(&metronome) {
                 commands.entity(bullet_launcher_entity).try_despawn();
-            } else if !bullet_launcher.timer.finished()
-                && bullet_launcher.last_fired_on_beat.map_or_else(
-                    || true,
-                    |b| metronome.is_beat_start_frame && b != bullet_launcher.timer.beats_elapsed(),
-                )
-            {
-                bullet_launcher.last_fired_on_beat = Some(bullet_launcher.timer.beats_elapsed());
-                commands.spawn((
-                    Bullet {
-                        velocity: bullet_launcher.velocity,
-                        damage: bullet_launcher.damage,
-                        target: None,
-                    },
-                    Transform::from_xyz(
-                        parent_transform.translation.x,
-                        parent_transform.translation.y,
-                        2.,
-                    ),
-                    Velocity::zero(),
-                    AudioPlayer::new(bullet_sfx.fire.clone()),
-                    Mesh2d(meshes.add(Circle::new(bullet_launcher.radius))),
-                    MeshMaterial2d(materials.add(Color::hsva(1., 1., 1., 1.))),
-                    Collider::ball(bullet_launcher.radius),
-                    Sensor,
-                    RigidBody::KinematicVelocityBased,
-                    ActiveCollisionTypes::default() | ActiveCollisionTypes::KINEMATIC_STATIC,
-                ));
+            } else if !bullet_launcher.timer.finished() {
+                let beats_elapsed = bullet_launcher.timer.beats_elapsed();
+                // Fire once for every beat crossed since the last shot
+                let shots = bullet_launcher
+                    .last_fired_on_beat
+                    .map_or(1, |b| beats_elapsed.saturating_sub(b))
+                    .saturating_mul(bullet_launcher.bullets_per_beat);
+                bullet_launcher.last_fired_on_beat = Some(beats_elapsed);
+                for _ in 0..shots {
+                    commands.spawn((
+                        DespawnOnExit(InRun),
+                        Bullet {
+                            velocity: bullet_launcher.velocity,
+                            damage: bullet_launcher.damage,
+                            target: None,
+                            element: bullet_launcher.element,
+                        },
+                        Transform::from_xyz(
+                            parent_transform.translation.x,
+                            parent_transform.translation.y,
+                            2.,
+                        )
+                        .with_scale(Vec3::splat(bullet_launcher.radius)),
+                        Velocity::zero(),
+                        AudioPlayer::new(bullet_sfx.fire.clone()),
+                        Mesh2d(shared_meshes.circle.clone()),
+                        MeshMaterial2d(
+                            materials.add(
+                                bullet_launcher
+                                    .element
+                                    .map_or(Color::hsva(1., 1., 1., 1.), Element::color),
+                            ),
+                        ),
+                        Collider::ball(1.),
+                        Sensor,
+                        RigidBody::KinematicVelocityBased,
+                        ActiveCollisionTypes::default() | ActiveCollisionTypes::KINEMATIC_STATIC,
+                    ));
+                }
             }
         }
     }
 }
 
+/// Fades explosions out and despawns them
+#[allow(clippy::needless_pass_by_value)]
+pub fn explosion_system(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut query: Query<(Entity, &mut Explosion, &MeshMaterial2d<ColorMaterial>)>,
+) {
+    for (entity, mut explosion, material) in &mut query {
+        if explosion.timer.tick(time.delta()).just_finished() {
+            commands.entity(entity).try_despawn();
+        } else if let Some(material) = materials.get_mut(&material.0) {
+            material
+                .color
+                .set_alpha(0.6 * explosion.timer.fraction_remaining());
+        }
+    }
+}
+
 #[allow(clippy::needless_pass_by_value)]
 pub fn bullet_system(
     mut bullet_query: Query<(&mut Bullet, &mut Velocity, &Transform)>,
@@ -135,19 +210,63 @@ pub fn bullet_system(
 }
 
 #[allow(clippy::needless_pass_by_value)]
+#[allow(clippy::too_many_arguments)]
 pub fn bullet_collision_system(
     mut commands: Commands,
     rapier_context: ReadRapierContext,
-    mut bullet_query: Query<(Entity, &Bullet)>,
-    mut enemy_query: Query<(Entity, &mut Health), With<Enemy>>,
+    shared_meshes: Res<SharedMeshes>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut damage_events: MessageWriter<DamageEvent>,
+    mut apply_status_effects: MessageWriter<ApplyStatusEffect>,
+    mut bullet_query: Query<(Entity, &Bullet, &Transform)>,
+    enemy_query: Query<(Entity, &Transform), With<Enemy>>,
     blocks_projectiles_query: Query<Entity, With<BlocksProjectiles>>,
 ) {
-    for (bullet_entity, bullet) in &mut bullet_query {
+    for (bullet_entity, bullet, bullet_transform) in &mut bullet_query {
         let rapier_context = rapier_context.single().unwrap();
-        for (enemy_entity, mut health) in &mut enemy_query {
+        for (enemy_entity, _) in enemy_query {
             if rapier_context.intersection_pair(bullet_entity, enemy_entity) == Some(true) {
-                health.current_health = health.current_health.saturating_sub(bullet.damage);
+                damage_events.write(DamageEvent {
+                    source: bullet_entity,
+                    target: enemy_entity,
+                    amount: bullet.damage,
+                    kind: DamageKind::Bullet,
+                });
                 commands.entity(bullet_entity).try_despawn();
+                if bullet.element == Some(Element::Fire) {
+                    let center = bullet_transform.translation.xy();
+                    for (caught_entity, caught_transform) in enemy_query {
+                        if caught_transform.translation.xy().distance(center)
+                            <= FIRE_EXPLOSION_RADIUS
+                        {
+                            if caught_entity != enemy_entity {
+                                damage_events.write(DamageEvent {
+                                    source: bullet_entity,
+                                    target: caught_entity,
+                                    amount: bullet.damage,
+                                    kind: DamageKind::Bullet,
+                                });
+                            }
+                            apply_status_effects.write(ApplyStatusEffect {
+                                source: bullet_entity,
+                                target: caught_entity,
+                                kind: StatusEffectKind::Burn,
+                                beats: BURN_STEPS,
+                            });
+                        }
+                    }
+                    commands.spawn((
+                        DespawnOnExit(InRun),
+                        Explosion {
+                            timer: Timer::from_seconds(0.3, TimerMode::Once),
+                        },
+                        Mesh2d(shared_meshes.circle.clone()),
+                        MeshMaterial2d(materials.add(Element::Fire.color().with_alpha(0.6))),
+                        Transform::from_xyz(center.x, center.y, 3.)
+                            .with_scale(Vec3::splat(FIRE_EXPLOSION_RADIUS)),
+                    ));
+                }
+                break;
             }
         }
         for blocks_projectiles_entity in blocks_projectiles_query {
@@ -159,3 +278,18 @@ pub fn bullet_collision_system(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::score::Dynamic;
+
+    #[test]
+    fn fortissimo_launchers_outdo_pianissimo_ones() {
+        let [pp, ff] = [Dynamic::Pp, Dynamic::Ff].map(|dynamic| {
+            bullet_launcher_bundle(3., 150., bullet_stats(dynamic), 4, None).bullet_launcher
+        });
+        assert!(pp.damage < ff.damage);
+        assert!(pp.bullets_per_beat < ff.bullets_per_beat);
+    }
+}
@@ -0,0 +1,262 @@
+use bevy::{audio::Volume, log, prelude::*};
+use bevy_enhanced_input::prelude::{Press, *};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Song,
+    chart::{Chart, SongChart},
+    game_state::GameState,
+    metronome::{BeatCrossed, Metronome, down_beats, is_down_beat, nearest_beat},
+    spawn_song,
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+const CALIBRATION_FILE: &str = "calibration.ron";
+const TAPS_PER_PHASE: usize = 16;
+
+/// How late the player hears the song and how late their inputs reach us
+#[derive(Resource, Default, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct LatencyOffsets {
+    pub audio_nanos: i64,
+    pub input_nanos: i64,
+}
+
+impl LatencyOffsets {
+    /// Delay between a beat in the song and the input of a player tapping along to it
+    pub const fn total_nanos(&self) -> i64 {
+        self.audio_nanos + self.input_nanos
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn saved_latency_offsets() -> Option<LatencyOffsets> {
+    let contents = std::fs::read_to_string(CALIBRATION_FILE).ok()?;
+    ron::from_str(&contents)
+        .inspect_err(|error| log::warn!("Ignoring invalid {CALIBRATION_FILE}: {error}"))
+        .ok()
+}
+
+/// There is nowhere to keep the calibration in the browser, so it lasts a session
+#[cfg(target_arch = "wasm32")]
+pub const fn saved_latency_offsets() -> Option<LatencyOffsets> {
+    None
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn save_latency_offsets(latency_offsets: &LatencyOffsets) {
+    let result = ron::ser::to_string_pretty(latency_offsets, ron::ser::PrettyConfig::default())
+        .map_err(|error| error.to_string())
+        .and_then(|contents| {
+            std::fs::write(CALIBRATION_FILE, contents).map_err(|error| error.to_string())
+        });
+    if let Err(error) = result {
+        log::error!("Failed to save {CALIBRATION_FILE}: {error}");
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+const fn save_latency_offsets(_latency_offsets: &LatencyOffsets) {}
+
+pub fn setup_latency_offsets(mut commands: Commands) {
+    commands.insert_resource(saved_latency_offsets().unwrap_or_default());
+}
+
+#[derive(Component)]
+pub struct Calibration;
+
+#[derive(InputAction)]
+#[action_output(bool)]
+pub struct CalibrationTap;
+
+#[derive(InputAction)]
+#[action_output(bool)]
+pub struct SkipCalibration;
+
+/// The player first taps along to the click track, then to a silent flash
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CalibrationPhase {
+    Audio,
+    Visual,
+}
+
+#[derive(Resource)]
+pub struct CalibrationSession {
+    phase: CalibrationPhase,
+    taps_nanos: Vec<i64>,
+    audio_tap_nanos: i64,
+}
+
+#[derive(Component)]
+pub struct CalibrationText;
+
+#[derive(Component)]
+pub struct CalibrationFlash {
+    timer: Timer,
+}
+
+#[allow(clippy::needless_pass_by_value)]
+pub fn enter_calibration(
+    mut commands: Commands,
+    charts: Res<Assets<Chart>>,
+    song_chart: Res<SongChart>,
+    song_query: Query<Entity, With<Song>>,
+) {
+    for song_entity in &song_query {
+        commands.entity(song_entity).despawn();
+    }
+    if let Some(chart) = charts.get(&song_chart.0) {
+        spawn_song(&mut commands, chart, PlaybackSettings::default());
+    }
+
+    commands.insert_resource(CalibrationSession {
+        phase: CalibrationPhase::Audio,
+        taps_nanos: Vec::with_capacity(TAPS_PER_PHASE),
+        audio_tap_nanos: 0,
+    });
+    commands.spawn((
+        DespawnOnExit(GameState::Calibrating),
+        Calibration,
+        actions!(Calibration[(
+            Action::<CalibrationTap>::new(),
+            Press::default(),
+            bindings![KeyCode::Space, GamepadButton::South],
+        ),(
+            Action::<SkipCalibration>::new(),
+            Press::default(),
+            bindings![KeyCode::Enter, GamepadButton::East],
+        )]),
+    ));
+    commands.spawn((
+        DespawnOnExit(GameState::Calibrating),
+        Node {
+            width: Val::Percent(100.),
+            height: Val::Percent(100.),
+            flex_direction: FlexDirection::Column,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            row_gap: Val::Px(20.),
+            ..default()
+        },
+        BackgroundColor(Color::BLACK.with_alpha(0.8)),
+        children![
+            (
+                CalibrationText,
+                Text::new(""),
+                TextFont::from_font_size(24.),
+                TextLayout::new_with_justify(Justify::Center),
+            ),
+            (
+                CalibrationFlash {
+                    timer: Timer::from_seconds(0.1, TimerMode::Once),
+                },
+                Node {
+                    width: Val::Px(80.),
+                    height: Val::Px(80.),
+                    ..default()
+                },
+                BackgroundColor(Color::WHITE),
+                Visibility::Hidden,
+            )
+        ],
+    ));
+}
+
+/// The click track stops with the calibration, each run starts its own song
+#[allow(clippy::needless_pass_by_value)]
+pub fn exit_calibration(mut commands: Commands, song_query: Query<Entity, With<Song>>) {
+    for song_entity in &song_query {
+        commands.entity(song_entity).despawn();
+    }
+    commands.remove_resource::<CalibrationSession>();
+}
+
+fn median(values: &mut [i64]) -> i64 {
+    values.sort_unstable();
+    values.get(values.len() / 2).copied().unwrap_or(0)
+}
+
+#[allow(clippy::needless_pass_by_value)]
+pub fn record_calibration_tap(
+    _calibration_tap: On<Fire<CalibrationTap>>,
+    metronome: Res<Metronome>,
+    mut session: ResMut<CalibrationSession>,
+    mut latency_offsets: ResMut<LatencyOffsets>,
+    mut audio_sink: Query<&mut AudioSink, With<Song>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if metronome.total_beats == 0 {
+        return;
+    }
+    let Some((_, offset)) = nearest_beat(&metronome, down_beats(&metronome), 0) else {
+        return;
+    };
+    session
+        .taps_nanos
+        .push(offset.floor().try_into().unwrap_or(0));
+    if session.taps_nanos.len() < TAPS_PER_PHASE {
+        return;
+    }
+
+    // The median shrugs off the odd missed or doubled tap
+    let tap_nanos = median(&mut session.taps_nanos);
+    session.taps_nanos.clear();
+    match session.phase {
+        CalibrationPhase::Audio => {
+            session.audio_tap_nanos = tap_nanos;
+            session.phase = CalibrationPhase::Visual;
+            if let Ok(mut audio_sink) = audio_sink.single_mut() {
+                audio_sink.set_volume(Volume::SILENT);
+            }
+        }
+        CalibrationPhase::Visual => {
+            // Tapping to the flash only measures input latency, tapping to the click adds audio latency on top
+            *latency_offsets = LatencyOffsets {
+                audio_nanos: session.audio_tap_nanos - tap_nanos,
+                input_nanos: tap_nanos,
+            };
+            log::info!("Calibrated latency: {:?}", *latency_offsets);
+            save_latency_offsets(&latency_offsets);
+            next_state.set(GameState::MainMenu);
+        }
+    }
+}
+
+pub fn skip_calibration(
+    _skip_calibration: On<Fire<SkipCalibration>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    next_state.set(GameState::MainMenu);
+}
+
+#[allow(clippy::needless_pass_by_value)]
+pub fn calibration_display_system(
+    time: Res<Time>,
+    metronome: Res<Metronome>,
+    session: Res<CalibrationSession>,
+    mut beat_crossed: MessageReader<BeatCrossed>,
+    mut text_query: Query<&mut Text, With<CalibrationText>>,
+    mut flash_query: Query<(&mut CalibrationFlash, &mut Visibility)>,
+) {
+    if let Ok(mut text) = text_query.single_mut() {
+        let instructions = match session.phase {
+            CalibrationPhase::Audio => "Tap Space on every click",
+            CalibrationPhase::Visual => "Tap Space every time the square flashes",
+        };
+        text.0 = format!(
+            "{instructions}\n{}/{TAPS_PER_PHASE}\n\nPress Enter to skip",
+            session.taps_nanos.len()
+        );
+    }
+
+    if let Ok((mut flash, mut visibility)) = flash_query.single_mut() {
+        let flashed = beat_crossed
+            .read()
+            .any(|beat_crossed| is_down_beat(&metronome, beat_crossed.beat));
+        if flashed && session.phase == CalibrationPhase::Visual {
+            flash.timer.reset();
+            *visibility = Visibility::Visible;
+        } else if flash.timer.tick(time.delta()).just_finished() {
+            *visibility = Visibility::Hidden;
+        }
+    }
+}
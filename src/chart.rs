@@ -0,0 +1,344 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, io::Reader},
+    prelude::*,
+    sprite::Anchor,
+};
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{
+    metronome::{
+        BeatCrossed, Metronome, Subdivision, TempoChange, TempoMap, TempoTransition, TimeSignature,
+        initial_metronome,
+    },
+    note_highway::{HIGHWAY_WIDTH, NoteHighway},
+};
+
+/// A playable song: its audio and everything the metronome needs to follow it
+#[derive(Asset, TypePath, Debug)]
+pub struct Chart {
+    pub audio: Handle<AudioSource>,
+    pub tempo_map: TempoMap,
+    pub first_beat_offset_nanos: u64,
+    pub time_signature: TimeSignature,
+    pub subdivision: Subdivision,
+    pub length_nanos: u64,
+    pub sections: Vec<Section>,
+}
+
+impl Chart {
+    /// Section playing during `measure`, `None` before the first one starts
+    fn section_at(&self, measure: u64) -> Option<&Section> {
+        self.sections
+            .iter()
+            .rev()
+            .find(|section| section.measure <= measure)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Section {
+    pub name: String,
+    pub measure: u64,
+}
+
+/// On-disk layout of a `.chart.ron` file
+#[derive(Deserialize)]
+struct ChartFile {
+    audio: String,
+    bpm: u64,
+    #[serde(default)]
+    tempo_changes: Vec<TempoChange>,
+    #[serde(default)]
+    first_beat_offset_ms: u64,
+    time_signature: TimeSignature,
+    subdivision: Subdivision,
+    length_ms: u64,
+    #[serde(default)]
+    sections: Vec<Section>,
+}
+
+#[derive(Debug, Error)]
+pub enum ChartError {
+    #[error("could not read chart: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse chart: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("chart has no audio file")]
+    MissingAudio,
+    #[error("tempo must be above 0 bpm (at step {beat})")]
+    ZeroTempo { beat: u64 },
+    #[error("tempo change at step {beat} is not after the previous one")]
+    UnorderedTempoChange { beat: u64 },
+    #[error("tempo ramp at step {beat} lasts 0 steps")]
+    EmptyTempoRamp { beat: u64 },
+    #[error("invalid time signature {beats_per_measure}/{beat_unit}")]
+    InvalidTimeSignature {
+        beats_per_measure: u8,
+        beat_unit: u8,
+    },
+    #[error("first beat at {first_beat_offset_ms}ms is past the end of the song")]
+    FirstBeatAfterEnd { first_beat_offset_ms: u64 },
+    #[error("section {name:?} is not after the previous section")]
+    UnorderedSection { name: String },
+}
+
+impl ChartFile {
+    fn validate(&self) -> Result<(), ChartError> {
+        if self.audio.trim().is_empty() {
+            return Err(ChartError::MissingAudio);
+        }
+        if self.bpm == 0 {
+            return Err(ChartError::ZeroTempo { beat: 0 });
+        }
+
+        let mut previous_beat = None;
+        for change in &self.tempo_changes {
+            if change.bpm == 0 {
+                return Err(ChartError::ZeroTempo { beat: change.beat });
+            }
+            if change.beat == 0 || previous_beat.is_some_and(|beat| change.beat <= beat) {
+                return Err(ChartError::UnorderedTempoChange { beat: change.beat });
+            }
+            if change.transition == (TempoTransition::Ramp { beats: 0 }) {
+                return Err(ChartError::EmptyTempoRamp { beat: change.beat });
+            }
+            previous_beat = Some(change.beat);
+        }
+
+        let TimeSignature {
+            beats_per_measure,
+            beat_unit,
+        } = self.time_signature;
+        let steps_per_beat_unit = self.subdivision.steps_per_beat_unit(self.time_signature);
+        if beats_per_measure == 0
+            || !matches!(beat_unit, 1 | 2 | 4 | 8 | 16)
+            || beats_per_measure.checked_mul(steps_per_beat_unit).is_none()
+        {
+            return Err(ChartError::InvalidTimeSignature {
+                beats_per_measure,
+                beat_unit,
+            });
+        }
+
+        if self.first_beat_offset_ms >= self.length_ms {
+            return Err(ChartError::FirstBeatAfterEnd {
+                first_beat_offset_ms: self.first_beat_offset_ms,
+            });
+        }
+
+        for sections in self.sections.windows(2) {
+            if sections[1].measure <= sections[0].measure {
+                return Err(ChartError::UnorderedSection {
+                    name: sections[1].name.clone(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct ChartLoader;
+
+impl AssetLoader for ChartLoader {
+    type Asset = Chart;
+    type Settings = ();
+    type Error = ChartError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<Chart, ChartError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let chart_file: ChartFile = ron::de::from_bytes(&bytes)?;
+        chart_file.validate()?;
+
+        let tempo_map = chart_file
+            .tempo_changes
+            .into_iter()
+            .fold(TempoMap::new(chart_file.bpm), TempoMap::with_change);
+
+        Ok(Chart {
+            audio: load_context.load(chart_file.audio),
+            tempo_map,
+            first_beat_offset_nanos: chart_file.first_beat_offset_ms * 1_000_000,
+            time_signature: chart_file.time_signature,
+            subdivision: chart_file.subdivision,
+            length_nanos: chart_file.length_ms * 1_000_000,
+            sections: chart_file.sections,
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["chart.ron"]
+    }
+}
+
+/// The chart picked for the current run
+#[derive(Resource)]
+pub struct SongChart(pub Handle<Chart>);
+
+pub fn chart_metronome(chart: &Chart) -> Metronome {
+    let mut metronome = initial_metronome(
+        chart.tempo_map.clone(),
+        chart.time_signature,
+        chart.subdivision,
+    );
+    metronome.first_beat_offset_nanos = chart.first_beat_offset_nanos;
+    metronome
+}
+
+/// Name of the section being played, shown beside the note highway
+#[derive(Component)]
+pub struct SectionText;
+
+#[allow(clippy::needless_pass_by_value)]
+pub fn setup_section_text(
+    mut commands: Commands,
+    note_highway_query: Query<Entity, With<NoteHighway>>,
+) {
+    if let Ok(note_highway_entity) = note_highway_query.single() {
+        commands.entity(note_highway_entity).with_child((
+            SectionText,
+            Text2d::new(""),
+            TextFont::from_font_size(12.),
+            TextColor(Color::WHITE),
+            Anchor::CENTER_RIGHT,
+            Transform::from_xyz(-HIGHWAY_WIDTH / 2. - 8., 0., 13.),
+        ));
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+pub fn section_text_system(
+    charts: Res<Assets<Chart>>,
+    song_chart: Res<SongChart>,
+    mut beat_crossed: MessageReader<BeatCrossed>,
+    mut query: Query<&mut Text2d, With<SectionText>>,
+) {
+    let Some(measure) = beat_crossed.read().last().map(|crossed| crossed.measure) else {
+        return;
+    };
+    let Some(chart) = charts.get(&song_chart.0) else {
+        return;
+    };
+    let name = chart
+        .section_at(measure)
+        .map_or("", |section| section.name.as_str());
+    for mut text in &mut query {
+        if text.0 != name {
+            text.0 = name.to_string();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VALID_CHART: &str = r#"(
+        audio: "sounds/song.ogg",
+        bpm: 120,
+        tempo_changes: [
+            (beat: 16, bpm: 140, transition: Instant),
+            (beat: 32, bpm: 100, transition: Ramp(beats: 8)),
+        ],
+        first_beat_offset_ms: 0,
+        time_signature: (beats_per_measure: 4, beat_unit: 4),
+        subdivision: Sixteenths,
+        length_ms: 60000,
+        sections: [(name: "Intro", measure: 0), (name: "Verse", measure: 4)],
+    )"#;
+
+    /// Parses the valid chart with `from` swapped for `to`, then validates it
+    fn validate_with(from: &str, to: &str) -> Result<(), ChartError> {
+        assert_eq!(VALID_CHART.matches(from).count(), 1, "{from}");
+        let chart_file: ChartFile = ron::de::from_str(&VALID_CHART.replace(from, to))?;
+        chart_file.validate()
+    }
+
+    #[test]
+    fn valid_chart_passes() {
+        let chart_file: ChartFile = ron::de::from_str(VALID_CHART).unwrap();
+        assert!(chart_file.validate().is_ok());
+    }
+
+    #[test]
+    fn tempo_must_be_positive() {
+        assert!(matches!(
+            validate_with("bpm: 120", "bpm: 0"),
+            Err(ChartError::ZeroTempo { beat: 0 })
+        ));
+        assert!(matches!(
+            validate_with("bpm: 140", "bpm: 0"),
+            Err(ChartError::ZeroTempo { beat: 16 })
+        ));
+        assert!(matches!(
+            validate_with("bpm: 120", "bpm: -120"),
+            Err(ChartError::Ron(_))
+        ));
+    }
+
+    #[test]
+    fn tempo_changes_must_move_forward() {
+        assert!(matches!(
+            validate_with("beat: 32", "beat: 16"),
+            Err(ChartError::UnorderedTempoChange { beat: 16 })
+        ));
+        assert!(matches!(
+            validate_with("beat: 16", "beat: 0"),
+            Err(ChartError::UnorderedTempoChange { beat: 0 })
+        ));
+    }
+
+    #[test]
+    fn tempo_ramps_must_last_a_step() {
+        assert!(matches!(
+            validate_with("Ramp(beats: 8)", "Ramp(beats: 0)"),
+            Err(ChartError::EmptyTempoRamp { beat: 32 })
+        ));
+    }
+
+    #[test]
+    fn time_signature_must_be_playable() {
+        for time_signature in [
+            "(beats_per_measure: 0, beat_unit: 4)",
+            "(beats_per_measure: 4, beat_unit: 3)",
+            "(beats_per_measure: 100, beat_unit: 4)",
+        ] {
+            assert!(
+                matches!(
+                    validate_with("(beats_per_measure: 4, beat_unit: 4)", time_signature),
+                    Err(ChartError::InvalidTimeSignature { .. })
+                ),
+                "{time_signature}"
+            );
+        }
+    }
+
+    #[test]
+    fn first_beat_must_come_before_the_end() {
+        assert!(matches!(
+            validate_with("first_beat_offset_ms: 0", "first_beat_offset_ms: 60000"),
+            Err(ChartError::FirstBeatAfterEnd {
+                first_beat_offset_ms: 60000
+            })
+        ));
+    }
+
+    #[test]
+    fn sections_must_move_forward() {
+        assert!(matches!(
+            validate_with(
+                r#"(name: "Verse", measure: 4)"#,
+                r#"(name: "Verse", measure: 0)"#
+            ),
+            Err(ChartError::UnorderedSection { name }) if name == "Verse"
+        ));
+    }
+}
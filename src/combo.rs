@@ -0,0 +1,153 @@
+use bevy::{prelude::*, sprite::Anchor};
+
+use crate::{
+    judgement::{Judgement, JudgementTier},
+    note_highway::{HIGHWAY_WIDTH, NoteHighway},
+};
+
+/// Inputs landed on beat in a row, notes and slides alike
+#[derive(Resource, Debug, Default)]
+pub struct Combo {
+    pub count: u32,
+    pub best: u32,
+}
+
+impl Combo {
+    /// How much a long combo boosts ability damage and experience
+    pub const fn groove_multiplier(&self) -> f32 {
+        match self.count {
+            0..10 => 1.,
+            10..25 => 1.25,
+            25..50 => 1.5,
+            _ => 2.,
+        }
+    }
+}
+
+/// `amount` boosted by the groove multiplier, rounded to the nearest whole amount
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+pub fn with_groove(amount: u128, combo: &Combo) -> u128 {
+    (amount as f32 * combo.groove_multiplier()).round() as u128
+}
+
+pub fn reset_combo(mut commands: Commands) {
+    commands.insert_resource(Combo::default());
+}
+
+pub fn combo_system(mut judgements: MessageReader<Judgement>, mut combo: ResMut<Combo>) {
+    for judgement in judgements.read() {
+        if judgement.tier == JudgementTier::Miss {
+            combo.count = 0;
+        } else {
+            combo.count += 1;
+            combo.best = combo.best.max(combo.count);
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct ComboText;
+
+#[allow(clippy::needless_pass_by_value)]
+pub fn setup_combo_text(
+    mut commands: Commands,
+    note_highway_query: Query<Entity, With<NoteHighway>>,
+) {
+    if let Ok(note_highway_entity) = note_highway_query.single() {
+        commands.entity(note_highway_entity).with_child((
+            ComboText,
+            Text2d::new(""),
+            TextFont::from_font_size(12.),
+            TextColor(Color::WHITE),
+            Anchor::CENTER_LEFT,
+            Transform::from_xyz(HIGHWAY_WIDTH / 2. + 8., 0., 13.),
+        ));
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+pub fn combo_text_system(combo: Res<Combo>, mut query: Query<&mut Text2d, With<ComboText>>) {
+    if !combo.is_changed() {
+        return;
+    }
+    for mut text in &mut query {
+        text.0 = if combo.count == 0 {
+            String::new()
+        } else {
+            format!("{} combo\nx{}", combo.count, combo.groove_multiplier())
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NotePlayed, judgement::JudgedInput};
+    use JudgementTier::{Good, Great, Miss, Perfect};
+
+    fn judgement(tier: JudgementTier) -> Judgement {
+        Judgement {
+            input: JudgedInput::Note(NotePlayed::NorthNote),
+            beat: 0,
+            offset_nanos: 0,
+            tier,
+        }
+    }
+
+    #[test]
+    fn misses_break_the_combo_but_keep_the_best() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<Combo>()
+            .add_message::<Judgement>()
+            .add_systems(Update, combo_system);
+        let mut play = |tiers: &[JudgementTier]| {
+            for tier in tiers {
+                app.world_mut().write_message(judgement(*tier));
+            }
+            app.update();
+            let combo = app.world().resource::<Combo>();
+            (combo.count, combo.best)
+        };
+
+        assert_eq!(play(&[Perfect, Great, Good]), (3, 3));
+        assert_eq!(play(&[Miss]), (0, 3));
+        assert_eq!(play(&[Perfect, Perfect]), (2, 3));
+        assert_eq!(play(&[Great, Miss, Good]), (1, 3));
+        assert_eq!(play(&[Perfect, Perfect, Perfect]), (4, 4));
+    }
+
+    #[test]
+    fn groove_builds_up_with_the_combo() {
+        for (count, multiplier) in [
+            (0, 1.),
+            (9, 1.),
+            (10, 1.25),
+            (24, 1.25),
+            (25, 1.5),
+            (49, 1.5),
+            (50, 2.),
+            (500, 2.),
+        ] {
+            let combo = Combo { count, best: count };
+            assert!(
+                (combo.groove_multiplier() - multiplier).abs() < f32::EPSILON,
+                "{count}"
+            );
+        }
+        assert_eq!(
+            with_groove(
+                3,
+                &Combo {
+                    count: 10,
+                    best: 10
+                }
+            ),
+            4
+        );
+    }
+}
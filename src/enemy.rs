@@ -6,11 +6,15 @@ use rand::{Rng, rng};
 use crate::{
     MovementSpeed,
     bounce::initial_bounce,
-    health::{Health, health_bar_bundle},
+    experience::XpDrop,
+    game_state::InRun,
+    health::{Armor, DamageEvent, DamageKind, Health, Resistance, health_bar_bundle},
     map::BlocksProjectiles,
-    metronome::{Metronome, is_down_beat},
+    metronome::{BeatCrossed, Metronome, is_down_beat},
     player::Player,
+    player_health::ContactDamage,
     slide::initial_slide,
+    status_effect::{Immunities, StatusEffectKind, StatusEffects},
 };
 
 #[derive(Component, Debug)]
@@ -22,12 +26,14 @@ pub struct Raccoon {
     max_distance_squared_to_player: f32,
     bullet_radius: f32,
     bullet_velocity: f32,
+    bullet_damage: u128,
 }
 
 #[derive(Component, Debug)]
 pub struct RaccoonBullet {
     velocity: f32,
     direction: Vec2,
+    damage: u128,
 }
 
 #[derive(Component, Debug)]
@@ -67,13 +73,20 @@ pub fn spawn_skunk_system(
         sprite_transform.scale = Vec3::new(enemy_sprite_scale, enemy_sprite_scale, 0.);
 
         commands.spawn((
+            DespawnOnExit(InRun),
             Transform::from_xyz(spawn_pos.x, spawn_pos.y, 2.),
             RigidBody::Dynamic,
             LockedAxes::ROTATION_LOCKED,
             Collider::ball((45.0 / 2.0) * enemy_sprite_scale),
             MovementSpeed(6.),
-            Enemy,
-            Skunk,
+            (Enemy, StatusEffects::default()),
+            (Skunk, Immunities(vec![StatusEffectKind::Poison])),
+            XpDrop(1),
+            ContactDamage(1),
+            Resistance {
+                kind: DamageKind::Aoe,
+                multiplier: 0.5,
+            },
             Velocity::zero(),
             Health {
                 max_health: 5,
@@ -103,23 +116,28 @@ pub fn spawn_skunk_system(
 pub fn skunk_movement_system(
     mut commands: Commands,
     metronome: Res<Metronome>,
+    mut beat_crossed: MessageReader<BeatCrossed>,
     player_query: Query<&Transform, With<Player>>,
-    skunk_query: Query<(Entity, &MovementSpeed, &Transform), With<Skunk>>,
+    skunk_query: Query<(Entity, &MovementSpeed, &Transform, &StatusEffects), With<Skunk>>,
 ) {
     if metronome.started
-        && metronome.is_beat_start_frame
-        && is_down_beat(&metronome)
+        && beat_crossed
+            .read()
+            .any(|beat_crossed| is_down_beat(&metronome, beat_crossed.beat))
         && let Ok(player_transform) = player_query.single()
     {
-        for (entity, movement_speed, enemy_transform) in skunk_query {
+        for (entity, movement_speed, enemy_transform, status_effects) in skunk_query {
+            if status_effects.has(StatusEffectKind::Stun) {
+                continue;
+            }
             let mut rng = rng();
             let speed_variation = rng.random_range(-0.2..=0.2);
-            let varied_velocity = movement_speed.0 * (1.0 + speed_variation);
+            let varied_velocity =
+                movement_speed.0 * status_effects.speed_multiplier() * (1.0 + speed_variation);
             commands.entity(entity).try_insert(initial_slide(
                 varied_velocity,
                 player_transform.translation.xy() - enemy_transform.translation.xy(),
                 1,
-                &metronome,
             ));
         }
     }
@@ -153,18 +171,23 @@ pub fn spawn_raccoon_system(
         sprite_transform.scale = Vec3::new(enemy_sprite_scale, enemy_sprite_scale, 0.);
 
         commands.spawn((
+            DespawnOnExit(InRun),
             Transform::from_xyz(spawn_pos.x, spawn_pos.y, 2.),
             RigidBody::Dynamic,
             LockedAxes::ROTATION_LOCKED,
             Collider::ball((45.0 / 2.0) * enemy_sprite_scale),
             MovementSpeed(6.),
-            Enemy,
+            (Enemy, StatusEffects::default()),
             Raccoon {
                 min_distance_squared_to_player: 100.0 * 100.0,
                 max_distance_squared_to_player: 200.0 * 200.0,
                 bullet_radius: 5.0,
                 bullet_velocity: 30.0,
+                bullet_damage: 1,
             },
+            XpDrop(3),
+            ContactDamage(1),
+            Armor(1),
             Velocity::zero(),
             Health {
                 max_health: 5,
@@ -196,15 +219,20 @@ pub fn raccoon_movement_system(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
     metronome: Res<Metronome>,
+    mut beat_crossed: MessageReader<BeatCrossed>,
     player_query: Query<&Transform, With<Player>>,
-    raccoon_query: Query<(Entity, &MovementSpeed, &Transform, &Raccoon)>,
+    raccoon_query: Query<(Entity, &MovementSpeed, &Transform, &Raccoon, &StatusEffects)>,
 ) {
     if metronome.started
-        && metronome.is_beat_start_frame
-        && is_down_beat(&metronome)
+        && beat_crossed
+            .read()
+            .any(|beat_crossed| is_down_beat(&metronome, beat_crossed.beat))
         && let Ok(player_transform) = player_query.single()
     {
-        for (entity, movement_speed, raccoon_transform, raccoon) in raccoon_query {
+        for (entity, movement_speed, raccoon_transform, raccoon, status_effects) in raccoon_query {
+            if status_effects.has(StatusEffectKind::Stun) {
+                continue;
+            }
             let distance_squared_to_player = player_transform
                 .translation
                 .distance_squared(raccoon_transform.translation);
@@ -214,31 +242,33 @@ pub fn raccoon_movement_system(
             let mut rng = rng();
             if distance_squared_to_player < raccoon.min_distance_squared_to_player {
                 let speed_variation = rng.random_range(-0.2..=0.2);
-                let varied_velocity = movement_speed.0 * (1.0 + speed_variation);
+                let varied_velocity =
+                    movement_speed.0 * status_effects.speed_multiplier() * (1.0 + speed_variation);
                 let away_from_player =
                     raccoon_transform.translation.xy() - player_transform.translation.xy();
                 commands.entity(entity).try_insert(initial_slide(
                     varied_velocity,
                     away_from_player,
                     1,
-                    &metronome,
                 ));
             } else if distance_squared_to_player > raccoon.max_distance_squared_to_player {
                 let speed_variation = rng.random_range(-0.2..=0.2);
-                let varied_velocity = movement_speed.0 * (1.0 + speed_variation);
+                let varied_velocity =
+                    movement_speed.0 * status_effects.speed_multiplier() * (1.0 + speed_variation);
                 commands.entity(entity).try_insert(initial_slide(
                     varied_velocity,
                     towards_player,
                     1,
-                    &metronome,
                 ));
             } else {
                 let should_shoot = rng.random_range(0.0..=1.0) < 0.25;
                 if should_shoot {
                     commands.spawn((
+                        DespawnOnExit(InRun),
                         RaccoonBullet {
                             velocity: raccoon.bullet_velocity,
                             direction: towards_player,
+                            damage: raccoon.bullet_damage,
                         },
                         Transform::from_xyz(
                             raccoon_transform.translation.x,
@@ -262,16 +292,20 @@ pub fn raccoon_movement_system(
 #[allow(clippy::needless_pass_by_value)]
 pub fn raccoon_bullet_system(
     metronome: Res<Metronome>,
+    mut beat_crossed: MessageReader<BeatCrossed>,
     mut commands: Commands,
     bullet_query: Query<(Entity, &RaccoonBullet)>,
 ) {
-    if metronome.started && metronome.is_beat_start_frame && is_down_beat(&metronome) {
+    if metronome.started
+        && beat_crossed
+            .read()
+            .any(|beat_crossed| is_down_beat(&metronome, beat_crossed.beat))
+    {
         for (bullet_entity, bullet) in bullet_query {
             commands.entity(bullet_entity).try_insert(initial_slide(
                 bullet.velocity,
                 bullet.direction,
                 1,
-                &metronome,
             ));
         }
     }
@@ -281,16 +315,23 @@ pub fn raccoon_bullet_system(
 pub fn raccoon_bullet_collision_system(
     mut commands: Commands,
     rapier_context: ReadRapierContext,
-    bullet_query: Query<Entity, With<RaccoonBullet>>,
+    mut damage_events: MessageWriter<DamageEvent>,
+    bullet_query: Query<(Entity, &RaccoonBullet)>,
     player_query: Query<Entity, With<Player>>,
     blocks_projectiles_query: Query<Entity, With<BlocksProjectiles>>,
 ) {
-    for bullet_entity in bullet_query {
+    for (bullet_entity, bullet) in bullet_query {
         let rapier_context = rapier_context.single().unwrap();
         if let Ok(player_entity) = player_query.single()
             && rapier_context.intersection_pair(bullet_entity, player_entity) == Some(true)
         {
             commands.entity(bullet_entity).try_despawn();
+            damage_events.write(DamageEvent {
+                source: bullet_entity,
+                target: player_entity,
+                amount: bullet.damage,
+                kind: DamageKind::Projectile,
+            });
         }
         for blocks_projectiles_entity in blocks_projectiles_query {
             if rapier_context.intersection_pair(bullet_entity, blocks_projectiles_entity)
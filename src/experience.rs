@@ -0,0 +1,177 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    combo::{Combo, with_groove},
+    game_state::InRun,
+    level_up::PendingLevelUps,
+    player::Player,
+    shared_meshes::SharedMeshes,
+};
+
+/// Experience an enemy leaves behind when it dies
+#[derive(Component, Debug)]
+pub struct XpDrop(pub u32);
+
+#[derive(Component, Debug)]
+pub struct XpGem(u32);
+
+/// Scale applied to the shared gem mesh, which is one tall
+const XP_GEM_HEIGHT: f32 = 9.;
+
+/// Gems within `radius` of the player are pulled towards them at `speed`
+#[derive(Component, Debug)]
+pub struct Magnet {
+    pub radius: f32,
+    pub speed: f32,
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct Experience {
+    pub level: u32,
+    pub xp: u32,
+}
+
+/// Experience needed to go from `level` to the next one
+pub const fn xp_for_next_level(level: u32) -> u32 {
+    5 + 5 * level + level * level
+}
+
+#[derive(Component)]
+pub struct XpBarFill;
+
+#[derive(Component)]
+pub struct XpLevelText;
+
+pub fn reset_experience(mut commands: Commands) {
+    commands.insert_resource(Experience::default());
+}
+
+pub fn xp_gem_bundle(
+    shared_meshes: &SharedMeshes,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+    amount: u32,
+    position: Vec2,
+) -> impl Bundle {
+    (
+        DespawnOnExit(InRun),
+        XpGem(amount),
+        Transform::from_xyz(position.x, position.y, 1.).with_scale(Vec3::splat(XP_GEM_HEIGHT)),
+        Mesh2d(shared_meshes.gem.clone()),
+        MeshMaterial2d(materials.add(Color::hsva(170., 0.8, 1., 1.))),
+        RigidBody::KinematicPositionBased,
+        Collider::ball(4. / XP_GEM_HEIGHT),
+        Sensor,
+        ActiveEvents::COLLISION_EVENTS,
+        ActiveCollisionTypes::KINEMATIC_KINEMATIC,
+    )
+}
+
+#[allow(clippy::needless_pass_by_value)]
+pub fn xp_gem_magnet_system(
+    time: Res<Time>,
+    player_query: Query<(&Transform, &Magnet), With<Player>>,
+    mut gem_query: Query<&mut Transform, (With<XpGem>, Without<Player>)>,
+) {
+    if let Ok((player_transform, magnet)) = player_query.single() {
+        let player_position = player_transform.translation.xy();
+        for mut gem_transform in &mut gem_query {
+            let to_player = player_position - gem_transform.translation.xy();
+            if to_player.length_squared() <= magnet.radius * magnet.radius {
+                let step = to_player.clamp_length_max(magnet.speed * time.delta_secs());
+                gem_transform.translation += step.extend(0.);
+            }
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+pub fn xp_gem_collision_system(
+    mut commands: Commands,
+    mut collision_events: MessageReader<CollisionEvent>,
+    combo: Res<Combo>,
+    mut experience: ResMut<Experience>,
+    mut pending_level_ups: ResMut<PendingLevelUps>,
+    gem_query: Query<&XpGem>,
+    player_query: Query<(), With<Player>>,
+) {
+    for collision_event in collision_events.read() {
+        if let CollisionEvent::Started(entity1, entity2, _) = collision_event {
+            let (gem_entity, gem) = if let Ok(gem) = gem_query.get(*entity1)
+                && player_query.contains(*entity2)
+            {
+                (*entity1, gem)
+            } else if let Ok(gem) = gem_query.get(*entity2)
+                && player_query.contains(*entity1)
+            {
+                (*entity2, gem)
+            } else {
+                continue;
+            };
+
+            commands.entity(gem_entity).try_despawn();
+            experience.xp +=
+                u32::try_from(with_groove(u128::from(gem.0), &combo)).unwrap_or(u32::MAX);
+            while experience.xp >= xp_for_next_level(experience.level) {
+                experience.xp -= xp_for_next_level(experience.level);
+                experience.level += 1;
+                pending_level_ups.0 += 1;
+            }
+        }
+    }
+}
+
+pub fn setup_xp_bar(mut commands: Commands) {
+    commands.spawn((
+        DespawnOnExit(InRun),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(8.),
+            left: Val::Percent(20.),
+            width: Val::Percent(60.),
+            height: Val::Px(16.),
+            ..default()
+        },
+        BackgroundColor(Color::BLACK.with_alpha(0.6)),
+        children![
+            (
+                XpBarFill,
+                Node {
+                    width: Val::Percent(0.),
+                    height: Val::Percent(100.),
+                    ..default()
+                },
+                BackgroundColor(Color::hsva(170., 0.8, 1., 1.)),
+            ),
+            (
+                XpLevelText,
+                Text::new("Lv 1"),
+                TextFont::from_font_size(12.),
+                Node {
+                    position_type: PositionType::Absolute,
+                    left: Val::Px(4.),
+                    ..default()
+                },
+            )
+        ],
+    ));
+}
+
+#[allow(clippy::needless_pass_by_value)]
+pub fn xp_bar_system(
+    experience: Res<Experience>,
+    mut fill_query: Query<&mut Node, With<XpBarFill>>,
+    mut text_query: Query<&mut Text, With<XpLevelText>>,
+) {
+    if !experience.is_changed() {
+        return;
+    }
+    #[allow(clippy::cast_precision_loss)]
+    let fraction = experience.xp as f32 / xp_for_next_level(experience.level) as f32;
+    for mut node in &mut fill_query {
+        node.width = Val::Percent(fraction * 100.);
+    }
+    for mut text in &mut text_query {
+        text.0 = format!("Lv {}", experience.level + 1);
+    }
+}
@@ -0,0 +1,303 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_enhanced_input::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    Song,
+    chart::{Chart, SongChart},
+    combo::Combo,
+    enemy::EnemySpawnTimer,
+    experience::Experience,
+    metronome::{Metronome, down_beats, nanos_for_beats, steps_per_measure},
+    player::Player,
+    spawn_song,
+};
+
+#[derive(States, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum GameState {
+    #[default]
+    Loading,
+    Calibrating,
+    MainMenu,
+    /// Counting in the first measure before the song starts
+    Countdown,
+    Playing,
+    /// Picking a new note after levelling up
+    LevelUp,
+    /// Arranging notes on the instruments' scores
+    EditingScore,
+    Paused,
+    /// The player ran out of health
+    GameOver,
+    /// Survived until the end of the song
+    Victory,
+}
+
+/// Whether a run is in progress, from the countdown until leaving the end screen.
+/// Everything spawned for a run is despawned when it ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InRun;
+
+impl ComputedStates for InRun {
+    type SourceStates = GameState;
+
+    fn compute(game_state: GameState) -> Option<Self> {
+        match game_state {
+            GameState::Countdown
+            | GameState::Playing
+            | GameState::LevelUp
+            | GameState::EditingScore
+            | GameState::Paused
+            | GameState::GameOver
+            | GameState::Victory => Some(Self),
+            GameState::Loading | GameState::Calibrating | GameState::MainMenu => None,
+        }
+    }
+}
+
+pub fn confirm_pressed(input: &ButtonInput<KeyCode>, gamepads: &Query<&Gamepad>) -> bool {
+    input.just_pressed(KeyCode::Enter)
+        || gamepads
+            .iter()
+            .any(|gamepad| gamepad.just_pressed(GamepadButton::Start))
+}
+
+fn overlay(text: impl Into<String>, font_size: f32) -> impl Bundle {
+    (
+        Node {
+            width: Val::Percent(100.),
+            height: Val::Percent(100.),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        BackgroundColor(Color::BLACK.with_alpha(0.6)),
+        children![(
+            Text::new(text),
+            TextFont::from_font_size(font_size),
+            TextLayout::new_with_justify(Justify::Center),
+        )],
+    )
+}
+
+pub fn setup_main_menu(mut commands: Commands) {
+    commands.spawn((
+        DespawnOnExit(GameState::MainMenu),
+        overlay(
+            "Bevy Orchestral\n\nPress Enter to play\nPress C to calibrate latency",
+            32.,
+        ),
+    ));
+}
+
+#[allow(clippy::needless_pass_by_value)]
+pub fn main_menu_system(
+    input: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if confirm_pressed(&input, &gamepads) {
+        next_state.set(GameState::Countdown);
+    } else if input.just_pressed(KeyCode::KeyC) {
+        next_state.set(GameState::Calibrating);
+    }
+}
+
+/// Rewinds the song and resets everything a run keeps track of
+#[allow(clippy::needless_pass_by_value)]
+pub fn start_run(
+    mut commands: Commands,
+    charts: Res<Assets<Chart>>,
+    song_chart: Res<SongChart>,
+    song_query: Query<Entity, With<Song>>,
+) {
+    for song_entity in &song_query {
+        commands.entity(song_entity).despawn();
+    }
+    if let Some(chart) = charts.get(&song_chart.0) {
+        spawn_song(&mut commands, chart, PlaybackSettings::default().paused());
+    }
+    commands.insert_resource(EnemySpawnTimer {
+        skunk_timer: Timer::from_seconds(2., TimerMode::Repeating),
+        raccoon_timer: Timer::from_seconds(3.5, TimerMode::Repeating),
+    });
+}
+
+#[derive(Resource)]
+pub struct Countdown {
+    timer: Timer,
+    down_beats_left: usize,
+}
+
+#[derive(Component)]
+pub struct CountdownText;
+
+/// Counts in one measure, a number per down beat, at the song's opening tempo
+#[allow(clippy::needless_pass_by_value)]
+pub fn start_countdown(mut commands: Commands, metronome: Res<Metronome>) {
+    let down_beats_per_measure = down_beats(&metronome).len().max(1);
+    #[allow(clippy::cast_possible_truncation)]
+    let steps_per_down_beat = steps_per_measure(&metronome) / down_beats_per_measure as u8;
+    commands.insert_resource(Countdown {
+        timer: Timer::new(
+            Duration::from_nanos(nanos_for_beats(&metronome, u64::from(steps_per_down_beat))),
+            TimerMode::Repeating,
+        ),
+        down_beats_left: down_beats_per_measure,
+    });
+    commands.spawn((
+        DespawnOnExit(GameState::Countdown),
+        CountdownText,
+        Text::new(down_beats_per_measure.to_string()),
+        TextFont::from_font_size(64.),
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.),
+            top: Val::Percent(40.),
+            ..default()
+        },
+        TextLayout::new_with_justify(Justify::Center),
+    ));
+}
+
+#[allow(clippy::needless_pass_by_value)]
+pub fn countdown_system(
+    time: Res<Time>,
+    mut countdown: ResMut<Countdown>,
+    mut query: Query<&mut Text, With<CountdownText>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if countdown.timer.tick(time.delta()).just_finished() {
+        countdown.down_beats_left = countdown.down_beats_left.saturating_sub(1);
+        if countdown.down_beats_left == 0 {
+            next_state.set(GameState::Playing);
+        } else if let Ok(mut text) = query.single_mut() {
+            text.0 = countdown.down_beats_left.to_string();
+        }
+    }
+}
+
+/// Starts or resumes the song, physics and player input from where they stopped
+#[allow(clippy::needless_pass_by_value)]
+pub fn resume_run(
+    mut commands: Commands,
+    mut time: ResMut<Time<Virtual>>,
+    mut metronome: ResMut<Metronome>,
+    audio_sink: Query<&AudioSink, With<Song>>,
+    mut rapier_config: Query<&mut RapierConfiguration>,
+    player_query: Query<Entity, With<Player>>,
+) {
+    if let Ok(audio_sink) = audio_sink.single() {
+        audio_sink.play();
+    }
+    metronome.started = true;
+    time.unpause();
+    if let Ok(mut rapier_config) = rapier_config.single_mut() {
+        rapier_config.physics_pipeline_active = true;
+    }
+    for player_entity in &player_query {
+        commands
+            .entity(player_entity)
+            .insert(ContextActivity::<Player>::ACTIVE);
+    }
+}
+
+/// Freezes the song, physics, virtual time and player input where they are
+#[allow(clippy::needless_pass_by_value)]
+pub fn suspend_run(
+    mut commands: Commands,
+    mut time: ResMut<Time<Virtual>>,
+    mut metronome: ResMut<Metronome>,
+    audio_sink: Query<&AudioSink, With<Song>>,
+    mut rapier_config: Query<&mut RapierConfiguration>,
+    player_query: Query<Entity, With<Player>>,
+) {
+    if let Ok(audio_sink) = audio_sink.single() {
+        audio_sink.pause();
+    }
+    metronome.started = false;
+    time.pause();
+    if let Ok(mut rapier_config) = rapier_config.single_mut() {
+        rapier_config.physics_pipeline_active = false;
+    }
+    for player_entity in &player_query {
+        commands
+            .entity(player_entity)
+            .insert(ContextActivity::<Player>::INACTIVE);
+    }
+}
+
+/// Lets the world run again once the run's entities are gone
+pub fn end_run(
+    mut time: ResMut<Time<Virtual>>,
+    mut rapier_config: Query<&mut RapierConfiguration>,
+) {
+    time.unpause();
+    if let Ok(mut rapier_config) = rapier_config.single_mut() {
+        rapier_config.physics_pipeline_active = true;
+    }
+}
+
+pub fn setup_pause_screen(mut commands: Commands) {
+    commands.spawn((
+        DespawnOnExit(GameState::Paused),
+        overlay("Paused\n\nPress X to resume", 32.),
+    ));
+}
+
+#[allow(clippy::needless_pass_by_value)]
+pub fn victory_system(
+    metronome: Res<Metronome>,
+    charts: Res<Assets<Chart>>,
+    song_chart: Res<SongChart>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if let Some(chart) = charts.get(&song_chart.0)
+        && metronome.song_position_nanos >= chart.length_nanos
+    {
+        next_state.set(GameState::Victory);
+    }
+}
+
+pub fn setup_victory_screen(mut commands: Commands) {
+    commands.spawn((
+        DespawnOnExit(GameState::Victory),
+        overlay("Victory!\n\nPress Enter to return to the menu", 32.),
+    ));
+}
+
+#[allow(clippy::needless_pass_by_value)]
+pub fn setup_game_over_screen(
+    mut commands: Commands,
+    metronome: Res<Metronome>,
+    experience: Res<Experience>,
+    combo: Res<Combo>,
+) {
+    let seconds = metronome.song_position_nanos / 1_000_000_000;
+    commands.spawn((
+        DespawnOnExit(GameState::GameOver),
+        overlay(
+            format!(
+                "Game Over\n\nSurvived {}:{:02}\nReached level {}\nBest combo {}\n\nPress Enter to return to the menu",
+                seconds / 60,
+                seconds % 60,
+                experience.level + 1,
+                combo.best,
+            ),
+            32.,
+        ),
+    ));
+}
+
+#[allow(clippy::needless_pass_by_value)]
+pub fn end_screen_system(
+    input: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if confirm_pressed(&input, &gamepads) {
+        next_state.set(GameState::MainMenu);
+    }
+}
@@ -3,13 +3,23 @@ use bevy::{
     camera::visibility::Visibility,
     color::Color,
     ecs::prelude::*,
-    math::primitives::Rectangle,
-    mesh::{Mesh, Mesh2d},
+    log,
+    math::{Vec3, Vec3Swizzles},
+    mesh::Mesh2d,
+    platform::collections::HashSet,
     sprite_render::{ColorMaterial, MeshMaterial2d},
     transform::components::Transform,
 };
+use rand::{Rng, rng};
 
-use crate::enemy::Enemy;
+use crate::{
+    combo::{Combo, with_groove},
+    enemy::Enemy,
+    experience::{XpDrop, xp_gem_bundle},
+    player::Player,
+    player_health::Invulnerable,
+    shared_meshes::SharedMeshes,
+};
 
 #[derive(Component, Debug)]
 pub struct Health {
@@ -17,13 +27,151 @@ pub struct Health {
     pub current_health: u128,
 }
 
-pub fn despawn_enemy_on_zero_health(
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DamageKind {
+    Laser,
+    Bullet,
+    Aoe,
+    Contact,
+    Projectile,
+    Poison,
+    Burn,
+}
+
+impl DamageKind {
+    /// Damage dealt by the orchestra, which grooves and crits
+    const fn is_orchestral(self) -> bool {
+        matches!(self, Self::Laser | Self::Bullet | Self::Aoe)
+    }
+}
+
+#[derive(Message, Debug, Clone, Copy)]
+pub struct DamageEvent {
+    /// What dealt the damage, such as a laser, bullet, ring or the enemy that touched the player
+    pub source: Entity,
+    pub target: Entity,
+    pub amount: u128,
+    pub kind: DamageKind,
+}
+
+#[derive(Message, Debug, Clone, Copy)]
+pub struct EnemyKilled {
+    pub enemy: Entity,
+    /// Source of the damage that finished the enemy off
+    pub killer: Entity,
+}
+
+#[derive(Message, Debug, Clone, Copy)]
+pub struct PlayerHit {
+    pub player: Entity,
+}
+
+/// Taken off every hit, though a hit always deals at least 1 damage
+#[derive(Component, Debug)]
+pub struct Armor(pub u128);
+
+/// Scales damage of one kind
+#[derive(Component, Debug)]
+pub struct Resistance {
+    pub kind: DamageKind,
+    pub multiplier: f32,
+}
+
+const CRIT_CHANCE: f64 = 0.1;
+const CRIT_MULTIPLIER: u128 = 2;
+
+/// Applies every `DamageEvent`, the only place `Health` is changed
+#[allow(clippy::needless_pass_by_value)]
+pub fn damage_system(
+    combo: Res<Combo>,
+    mut damage_events: MessageReader<DamageEvent>,
+    mut enemy_killed: MessageWriter<EnemyKilled>,
+    mut player_hit: MessageWriter<PlayerHit>,
+    mut query: Query<(
+        &mut Health,
+        Option<&Armor>,
+        Option<&Resistance>,
+        Has<Enemy>,
+        Has<Player>,
+        Has<Invulnerable>,
+    )>,
+) {
+    let mut rng = rng();
+    let mut hit_this_frame = HashSet::new();
+    for damage_event in damage_events.read() {
+        let Ok((mut health, armor, resistance, is_enemy, is_player, is_invulnerable)) =
+            query.get_mut(damage_event.target)
+        else {
+            continue;
+        };
+        if health.current_health == 0
+            || (is_player && (is_invulnerable || hit_this_frame.contains(&damage_event.target)))
+        {
+            continue;
+        }
+
+        let mut amount = damage_event.amount;
+        if damage_event.kind.is_orchestral() {
+            amount = with_groove(amount, &combo);
+            if rng.random_bool(CRIT_CHANCE) {
+                amount *= CRIT_MULTIPLIER;
+            }
+        }
+        if let Some(resistance) = resistance
+            && resistance.kind == damage_event.kind
+        {
+            #[allow(
+                clippy::cast_possible_truncation,
+                clippy::cast_precision_loss,
+                clippy::cast_sign_loss
+            )]
+            {
+                amount = (amount as f32 * resistance.multiplier).round() as u128;
+            }
+        }
+        if let Some(armor) = armor
+            && damage_event.kind != DamageKind::Poison
+        {
+            amount = amount.saturating_sub(armor.0).max(amount.min(1));
+        }
+
+        health.current_health = health.current_health.saturating_sub(amount);
+        if is_player {
+            hit_this_frame.insert(damage_event.target);
+            player_hit.write(PlayerHit {
+                player: damage_event.target,
+            });
+        }
+        if is_enemy && health.current_health == 0 {
+            enemy_killed.write(EnemyKilled {
+                enemy: damage_event.target,
+                killer: damage_event.source,
+            });
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+pub fn despawn_killed_enemies(
     mut commands: Commands,
-    query: Query<(Entity, &Health), (With<Enemy>, Changed<Health>)>,
+    shared_meshes: Res<SharedMeshes>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut enemy_killed: MessageReader<EnemyKilled>,
+    query: Query<(&Transform, Option<&XpDrop>)>,
 ) {
-    for (entity, health) in query {
-        if health.current_health == 0 {
-            commands.entity(entity).despawn();
+    for EnemyKilled { enemy, killer } in enemy_killed.read() {
+        let Ok((transform, xp_drop)) = query.get(*enemy) else {
+            continue;
+        };
+        log::debug!("{enemy} killed by {killer}");
+        commands.entity(*enemy).try_despawn();
+        if let Some(xp_drop) = xp_drop {
+            commands.spawn(xp_gem_bundle(
+                &shared_meshes,
+                &mut materials,
+                xp_drop.0,
+                transform.translation.xy(),
+            ));
         }
     }
 }
@@ -53,31 +201,29 @@ pub struct CurrentHealthBar;
 pub fn on_health_bar_add(
     event: On<Add, HealthBar>,
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
+    shared_meshes: Res<SharedMeshes>,
     mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
     let mut health_bar = commands.entity(event.entity);
     health_bar.with_children(|p| {
         p.spawn((
-            Mesh2d(meshes.add(Rectangle::new(18., 2.))),
+            Mesh2d(shared_meshes.rectangle.clone()),
             MeshMaterial2d(materials.add(Color::hsva(0., 0., 1., 1.))),
-            Transform::from_xyz(0., 0., 0.),
+            Transform::from_xyz(0., 0., 0.).with_scale(Vec3::new(18., 2., 1.)),
         ));
         p.spawn((
             CurrentHealthBar,
-            Mesh2d(meshes.add(Rectangle::new(18., 2.))),
+            Mesh2d(shared_meshes.rectangle.clone()),
             MeshMaterial2d(materials.add(Color::hsva(1., 1., 1., 1.))),
-            Transform::from_xyz(0., 0., 1.),
+            Transform::from_xyz(0., 0., 1.).with_scale(Vec3::new(18., 2., 1.)),
         ));
     });
 }
 
 pub fn health_bar_system(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
     health_query: Query<(&Health, &Children), Changed<Health>>,
     mut health_bar_query: Query<(&mut Visibility, &Children), With<HealthBar>>,
-    current_health_bar_query: Query<Entity, With<CurrentHealthBar>>,
+    mut current_health_bar_query: Query<&mut Transform, With<CurrentHealthBar>>,
 ) {
     for (health, children) in health_query.iter() {
         for child in children.iter() {
@@ -89,14 +235,12 @@ pub fn health_bar_system(
                 }
 
                 for heatlh_bar_child in health_bar_children.iter() {
-                    if let Ok(current_health_bar) = current_health_bar_query.get(heatlh_bar_child) {
+                    if let Ok(mut transform) = current_health_bar_query.get_mut(heatlh_bar_child) {
                         #[allow(clippy::cast_precision_loss)]
                         let health_missing =
                             (health.current_health as f32 / health.max_health as f32) * 18.;
-                        commands.entity(current_health_bar).try_insert((
-                            Mesh2d(meshes.add(Rectangle::new(health_missing, 2.))),
-                            Transform::from_xyz(-(9. - health_missing / 2.), 0., 1.),
-                        ));
+                        transform.translation.x = -(9. - health_missing / 2.);
+                        transform.scale.x = health_missing;
                     }
                 }
             }
@@ -2,7 +2,7 @@ use bevy::prelude::*;
 use bevy_aseprite_ultra::prelude::{Animation, AnimationDirection, AnimationRepeat, AseAnimation};
 use bevy_rapier2d::prelude::{Collider, LockedAxes, RigidBody};
 
-use crate::{bounce::initial_bounce, follower::Follower};
+use crate::{bounce::initial_bounce, follower::Follower, game_state::InRun, score::Score};
 
 #[derive(Component)]
 pub struct Violin;
@@ -14,13 +14,16 @@ pub fn spawn_violin(
     following: Entity,
     follow_distance: f32,
     spawn_pos: Vec2,
+    score: Score,
 ) {
     let sprite_scale = 0.3;
     let mut sprite_transform = Transform::from_xyz(0., 0., 1.);
     sprite_transform.scale = Vec3::new(sprite_scale, sprite_scale, 0.);
 
     commands.spawn((
+        DespawnOnExit(InRun),
         Violin,
+        score,
         Follower {
             following,
             follow_distance,
@@ -55,13 +58,16 @@ pub fn spawn_tuba(
     following: Entity,
     follow_distance: f32,
     spawn_pos: Vec2,
+    score: Score,
 ) {
     let sprite_scale = 0.4;
     let mut sprite_transform = Transform::from_xyz(0., 0., 1.);
     sprite_transform.scale = Vec3::new(sprite_scale, sprite_scale, 0.);
 
     commands.spawn((
+        DespawnOnExit(InRun),
         Tuba,
+        score,
         Follower {
             following,
             follow_distance,
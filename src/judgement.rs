@@ -0,0 +1,412 @@
+use std::collections::HashSet;
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use fraction::Fraction;
+
+use crate::{
+    NotePlayed,
+    calibration::LatencyOffsets,
+    metronome::{
+        Metronome, MetronomeTimer, beat_at_song_nanos, down_beats, nanos_at_beat, nearest_beat,
+        steps_per_beat_unit, steps_per_measure,
+    },
+    note_highway::NoteHighway,
+    score::Score,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JudgementTier {
+    Perfect,
+    Great,
+    Good,
+    Miss,
+}
+
+impl JudgementTier {
+    /// How strongly an ability triggered with this accuracy should hit
+    pub const fn power_multiplier(self) -> f32 {
+        match self {
+            Self::Perfect => 1.25,
+            Self::Great => 1.,
+            Self::Good => 0.75,
+            Self::Miss => 0.,
+        }
+    }
+
+    const fn label(self) -> &'static str {
+        match self {
+            Self::Perfect => "Perfect",
+            Self::Great => "Great",
+            Self::Good => "Good",
+            Self::Miss => "Miss",
+        }
+    }
+
+    pub const fn color(self) -> Color {
+        match self {
+            Self::Perfect => Color::hsva(50., 0.9, 1., 1.),
+            Self::Great => Color::hsva(120., 0.8, 1., 1.),
+            Self::Good => Color::hsva(200., 0.8, 1., 1.),
+            Self::Miss => Color::hsva(0., 0.9, 1., 1.),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JudgedInput {
+    Slide,
+    Note(NotePlayed),
+}
+
+#[derive(Message, Debug, Clone, Copy)]
+pub struct Judgement {
+    pub input: JudgedInput,
+    /// Step the input was judged against
+    pub beat: u8,
+    /// Negative when the input came before the beat, positive when after
+    pub offset_nanos: i64,
+    pub tier: JudgementTier,
+}
+
+impl Judgement {
+    pub const fn is_early(&self) -> bool {
+        self.offset_nanos < 0
+    }
+}
+
+/// Largest distance from a down beat, in nanoseconds, still awarding each tier
+#[derive(Resource)]
+pub struct JudgementWindows {
+    pub perfect: Fraction,
+    pub great: Fraction,
+    pub good: Fraction,
+}
+
+impl JudgementWindows {
+    fn tier(&self, offset: Fraction) -> JudgementTier {
+        let distance = if offset < Fraction::from(0) {
+            -offset
+        } else {
+            offset
+        };
+        if distance <= self.perfect {
+            JudgementTier::Perfect
+        } else if distance <= self.great {
+            JudgementTier::Great
+        } else if distance <= self.good {
+            JudgementTier::Good
+        } else {
+            JudgementTier::Miss
+        }
+    }
+}
+
+/// Inputs are ignored after a miss until this runs out
+#[derive(Component)]
+pub struct MissLockout {
+    timer: MetronomeTimer,
+}
+
+/// Scored notes already judged in each lane, by absolute step, so the unplayed ones can be missed
+#[derive(Resource, Default)]
+pub struct JudgedNotes {
+    judged: HashSet<(NotePlayed, u64)>,
+    /// Last step whose good window has closed
+    closed_step: Option<u64>,
+}
+
+pub fn reset_judged_notes(mut commands: Commands) {
+    commands.insert_resource(JudgedNotes::default());
+}
+
+#[derive(SystemParam)]
+pub struct Judge<'w, 's> {
+    metronome: Res<'w, Metronome>,
+    judgement_windows: Res<'w, JudgementWindows>,
+    latency_offsets: Res<'w, LatencyOffsets>,
+    judged_notes: ResMut<'w, JudgedNotes>,
+    judgements: MessageWriter<'w, Judgement>,
+    lockout_query: Query<'w, 's, (), With<MissLockout>>,
+    commands: Commands<'w, 's>,
+}
+
+impl Judge<'_, '_> {
+    /// Judges an input against the nearest of the beats it was expected on and publishes
+    /// the result. Inputs that weren't expected on any beat are judged against the nearest
+    /// down beat and always miss.
+    /// Returns `None` when the player is locked out from a previous miss.
+    pub fn judge(
+        &mut self,
+        player_entity: Entity,
+        input: JudgedInput,
+        expected_beats: Vec<u8>,
+    ) -> Option<Judgement> {
+        if self.lockout_query.contains(player_entity) {
+            return None;
+        }
+
+        let expected = !expected_beats.is_empty();
+        let beats = if expected {
+            expected_beats
+        } else {
+            down_beats(&self.metronome)
+        };
+        let (beat, offset) =
+            nearest_beat(&self.metronome, beats, self.latency_offsets.total_nanos())?;
+        let judgement = Judgement {
+            input,
+            beat,
+            offset_nanos: offset.floor().try_into().unwrap_or(0),
+            tier: if expected {
+                self.judgement_windows.tier(offset)
+            } else {
+                JudgementTier::Miss
+            },
+        };
+
+        if let JudgedInput::Note(lane) = input
+            && expected
+        {
+            #[allow(clippy::cast_possible_wrap)]
+            let beat_nanos = self.metronome.song_position_nanos as i64
+                - self.latency_offsets.total_nanos()
+                - judgement.offset_nanos;
+            if let Some(step) = u64::try_from(beat_nanos)
+                .ok()
+                .and_then(|nanos| beat_at_song_nanos(&self.metronome, nanos))
+            {
+                self.judged_notes.judged.insert((lane, step));
+            }
+        }
+
+        self.judgements.write(judgement);
+        if judgement.tier == JudgementTier::Miss {
+            self.commands.entity(player_entity).try_insert(MissLockout {
+                timer: MetronomeTimer::new(steps_per_beat_unit(&self.metronome)),
+            });
+        }
+        Some(judgement)
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+pub fn miss_lockout_system(
+    metronome: Res<Metronome>,
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut MissLockout)>,
+) {
+    for (entity, mut miss_lockout) in &mut query {
+        miss_lockout.timer.tick(&metronome);
+        if miss_lockout.timer.finished() {
+            commands.entity(entity).try_remove::<MissLockout>();
+        }
+    }
+}
+
+/// Misses every scored note whose good window closed without it being played
+#[allow(clippy::needless_pass_by_value)]
+pub fn passive_miss_system(
+    metronome: Res<Metronome>,
+    judgement_windows: Res<JudgementWindows>,
+    latency_offsets: Res<LatencyOffsets>,
+    mut judged_notes: ResMut<JudgedNotes>,
+    mut judgements: MessageWriter<Judgement>,
+    score_query: Query<&Score>,
+) {
+    if !metronome.started {
+        return;
+    }
+    #[allow(clippy::cast_possible_wrap)]
+    let now_nanos = metronome.song_position_nanos as i64 - latency_offsets.total_nanos();
+    let good_nanos: i64 = judgement_windows.good.floor().try_into().unwrap_or(0);
+    let Some(closed_step) = u64::try_from(now_nanos - good_nanos)
+        .ok()
+        .and_then(|nanos| beat_at_song_nanos(&metronome, nanos))
+    else {
+        return;
+    };
+
+    let steps_per_measure = u64::from(steps_per_measure(&metronome));
+    let first_step = judged_notes.closed_step.map_or(0, |step| step + 1);
+    for step in first_step..=closed_step {
+        #[allow(clippy::cast_possible_truncation)]
+        let beat = (step % steps_per_measure) as u8;
+        let lanes: HashSet<NotePlayed> = score_query
+            .iter()
+            .filter(|score| score.note_at(beat).is_some())
+            .map(Score::lane)
+            .collect();
+        for lane in lanes {
+            if !judged_notes.judged.contains(&(lane, step)) {
+                #[allow(clippy::cast_possible_wrap)]
+                judgements.write(Judgement {
+                    input: JudgedInput::Note(lane),
+                    beat,
+                    offset_nanos: now_nanos - nanos_at_beat(&metronome, step) as i64,
+                    tier: JudgementTier::Miss,
+                });
+            }
+        }
+    }
+    judged_notes.judged.retain(|&(_, step)| step > closed_step);
+    judged_notes.closed_step = Some(closed_step);
+}
+
+#[derive(Component)]
+pub struct JudgementText {
+    timer: Timer,
+}
+
+#[allow(clippy::needless_pass_by_value)]
+pub fn setup_judgement_text(
+    mut commands: Commands,
+    note_highway_query: Query<Entity, With<NoteHighway>>,
+) {
+    if let Ok(note_highway_entity) = note_highway_query.single() {
+        commands.entity(note_highway_entity).with_child((
+            JudgementText {
+                timer: Timer::from_seconds(0.5, TimerMode::Once),
+            },
+            Text2d::new(""),
+            TextFont::from_font_size(12.),
+            TextColor(Color::WHITE),
+            Transform::from_xyz(0., 0., 13.),
+            Visibility::Hidden,
+        ));
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+pub fn judgement_text_system(
+    time: Res<Time>,
+    mut judgements: MessageReader<Judgement>,
+    mut query: Query<(
+        &mut JudgementText,
+        &mut Text2d,
+        &mut TextColor,
+        &mut Visibility,
+    )>,
+) {
+    let Ok((mut judgement_text, mut text, mut text_color, mut visibility)) = query.single_mut()
+    else {
+        return;
+    };
+
+    if let Some(judgement) = judgements.read().last() {
+        text.0 = match judgement.tier {
+            JudgementTier::Perfect | JudgementTier::Miss => judgement.tier.label().to_string(),
+            tier if judgement.is_early() => format!("{} (early)", tier.label()),
+            tier => format!("{} (late)", tier.label()),
+        };
+        text_color.0 = judgement.tier.color();
+        *visibility = Visibility::Visible;
+        judgement_text.timer.reset();
+    } else if judgement_text.timer.tick(time.delta()).just_finished() {
+        *visibility = Visibility::Hidden;
+    } else {
+        text_color
+            .0
+            .set_alpha(judgement_text.timer.fraction_remaining());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        metronome::{Subdivision, TempoMap, TimeSignature, advance_conductor, initial_metronome},
+        score::{Dynamic, Note, NoteLength, NoteName},
+    };
+
+    const SCORED_BEAT: u8 = 4;
+
+    #[derive(Resource, Default)]
+    struct Judged(Vec<Judgement>);
+
+    fn advance_song(mut metronome: ResMut<Metronome>) {
+        advance_conductor(&mut metronome, Duration::from_millis(16), None);
+    }
+
+    fn play_scored_beat(mut judge: Judge) {
+        if judge.metronome.is_beat_start_frame && judge.metronome.beat == SCORED_BEAT {
+            judge.judge(
+                Entity::PLACEHOLDER,
+                JudgedInput::Note(NotePlayed::NorthNote),
+                vec![SCORED_BEAT],
+            );
+        }
+    }
+
+    fn collect_judgements(mut judgements: MessageReader<Judgement>, mut judged: ResMut<Judged>) {
+        judged.0.extend(judgements.read());
+    }
+
+    /// Plays one measure with a single scored note, returning every judgement made
+    fn judgements_over_a_measure(play: bool) -> Vec<Judgement> {
+        let mut metronome = initial_metronome(
+            TempoMap::new(120),
+            TimeSignature {
+                beats_per_measure: 4,
+                beat_unit: 4,
+            },
+            Subdivision::Sixteenths,
+        );
+        metronome.started = true;
+        let score = Score::new(&metronome, NotePlayed::NorthNote).with_note(
+            SCORED_BEAT,
+            Note {
+                length: NoteLength::Quarter,
+                dynamic: Dynamic::Mf,
+                name: NoteName::G,
+            },
+        );
+        let mut app = App::new();
+        app.add_message::<Judgement>()
+            .insert_resource(metronome)
+            .insert_resource(JudgementWindows {
+                perfect: Fraction::from(25u64 * 1_000_000),
+                great: Fraction::from(50u64 * 1_000_000),
+                good: Fraction::from(90u64 * 1_000_000),
+            })
+            .init_resource::<LatencyOffsets>()
+            .init_resource::<JudgedNotes>()
+            .init_resource::<Judged>()
+            .add_systems(
+                Update,
+                (advance_song, passive_miss_system, collect_judgements).chain(),
+            );
+        if play {
+            app.add_systems(Update, play_scored_beat.after(advance_song));
+        }
+        app.world_mut().spawn(score);
+        // A measure at 120bpm lasts two seconds
+        for _ in 0..125 {
+            app.update();
+        }
+        app.world_mut()
+            .resource_mut::<Judged>()
+            .0
+            .drain(..)
+            .collect()
+    }
+
+    #[test]
+    fn unplayed_note_is_missed_once_its_window_closes() {
+        let judgements = judgements_over_a_measure(false);
+        assert_eq!(judgements.len(), 1);
+        let judgement = judgements[0];
+        assert_eq!(judgement.input, JudgedInput::Note(NotePlayed::NorthNote));
+        assert_eq!(judgement.beat, SCORED_BEAT);
+        assert_eq!(judgement.tier, JudgementTier::Miss);
+        assert!(judgement.offset_nanos > 90_000_000);
+    }
+
+    #[test]
+    fn played_note_is_not_missed() {
+        let judgements = judgements_over_a_measure(true);
+        assert_eq!(judgements.len(), 1);
+        assert_ne!(judgements[0].tier, JudgementTier::Miss);
+    }
+}
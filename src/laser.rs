@@ -9,11 +9,16 @@ use rand::{Rng, rng};
 
 use crate::{
     enemy::Enemy,
-    health::Health,
+    health::{DamageEvent, DamageKind},
     metronome::{Metronome, MetronomeTimer},
     nearest_entity::find_nearest_entity,
+    score::{Dynamic, Element},
+    status_effect::{ApplyStatusEffect, StatusEffectKind},
 };
 
+/// Steps a poison laser's poison lasts after its last hit
+const POISON_STEPS: u8 = 8;
+
 #[derive(Component, Debug)]
 pub struct Laser {
     damage_per_beat: u128,
@@ -22,6 +27,7 @@ pub struct Laser {
     shooter: Entity,
     direction: Option<Vec2>,
     length: f32,
+    element: Option<Element>,
 }
 
 #[derive(Bundle)]
@@ -48,16 +54,44 @@ pub fn setup_laser_sfx(asset_server: Res<AssetServer>, mut commands: Commands) {
     });
 }
 
-#[allow(clippy::too_many_arguments)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LaserStats {
+    pub damage_per_beat: u128,
+    pub width: f32,
+    pub length: f32,
+}
+
+/// Louder lasers hit harder, wider and further
+pub const fn laser_stats(dynamic: Dynamic) -> LaserStats {
+    let (damage_per_beat, width, length) = match dynamic {
+        Dynamic::Ppp => (1, 4., 250.),
+        Dynamic::Pp => (1, 6., 300.),
+        Dynamic::P => (1, 8., 400.),
+        Dynamic::Mp => (1, 9., 450.),
+        Dynamic::Mf => (1, 10., 500.),
+        Dynamic::F => (2, 12., 550.),
+        Dynamic::Ff => (2, 14., 600.),
+        Dynamic::Fff => (3, 16., 700.),
+    };
+    LaserStats {
+        damage_per_beat,
+        width,
+        length,
+    }
+}
+
 pub fn laser_bundle(
     meshes: &mut ResMut<Assets<Mesh>>,
     materials: &mut ResMut<Assets<ColorMaterial>>,
     laser_sfx: &Res<LaserSFX>,
-    damage_per_beat: u128,
+    LaserStats {
+        damage_per_beat,
+        width,
+        length,
+    }: LaserStats,
     number_beats_duration: u8,
-    width: f32,
-    length: f32,
     shooter: Entity,
+    element: Option<Element>,
 ) -> LaserBundle {
     LaserBundle {
         laser: Laser {
@@ -67,10 +101,17 @@ pub fn laser_bundle(
             direction: None,
             length,
             shooter,
+            element,
         },
         audio_player: AudioPlayer::new(laser_sfx.fire.clone()),
         mesh: Mesh2d(meshes.add(Rectangle::new(width, length))),
-        mesh_material: MeshMaterial2d(materials.add(Color::hsva(1., 1., 1., 0.5))),
+        mesh_material: MeshMaterial2d(
+            materials.add(
+                element
+                    .map_or(Color::hsva(1., 1., 1., 1.), Element::color)
+                    .with_alpha(0.5),
+            ),
+        ),
         collider: Collider::cuboid(width / 2., length / 2.),
         collision_groups: CollisionGroups::new(Group::GROUP_2, Group::ALL),
         sensor: Sensor,
@@ -84,9 +125,11 @@ pub fn laser_system(
     rapier_context: ReadRapierContext,
     metronome: Res<Metronome>,
     mut commands: Commands,
+    mut damage_events: MessageWriter<DamageEvent>,
+    mut apply_status_effects: MessageWriter<ApplyStatusEffect>,
     mut laser_query: Query<(Entity, &mut Laser)>,
     shooter_query: Query<&Transform, (Without<Enemy>, Without<Laser>)>,
-    mut enemy_query: Query<(Entity, &mut Health, &Transform), (With<Enemy>, Without<Laser>)>,
+    mut enemy_query: Query<(Entity, &Transform), (With<Enemy>, Without<Laser>)>,
 ) {
     let rapier_context = rapier_context.single().unwrap();
     for (laser_entity, mut laser) in &mut laser_query {
@@ -115,7 +158,7 @@ pub fn laser_system(
         if laser.timer.just_finished(&metronome) {
             commands.entity(laser_entity).try_despawn();
         } else if !laser.timer.finished() {
-            for (enemy_entity, mut health, _) in &mut enemy_query {
+            for (enemy_entity, _) in &enemy_query {
                 if rapier_context.intersection_pair(laser_entity, enemy_entity) == Some(true) {
                     let beats_elapsed = laser.timer.beats_elapsed();
                     let entities_damaged = laser
@@ -123,11 +166,64 @@ pub fn laser_system(
                         .entry(beats_elapsed)
                         .or_insert_with(HashSet::new);
                     if entities_damaged.insert(enemy_entity) {
-                        health.current_health =
-                            health.current_health.saturating_sub(laser.damage_per_beat);
+                        damage_events.write(DamageEvent {
+                            source: laser_entity,
+                            target: enemy_entity,
+                            amount: laser.damage_per_beat,
+                            kind: DamageKind::Laser,
+                        });
+                        if laser.element == Some(Element::Poison) {
+                            apply_status_effects.write(ApplyStatusEffect {
+                                source: laser_entity,
+                                target: enemy_entity,
+                                kind: StatusEffectKind::Poison,
+                                beats: POISON_STEPS,
+                            });
+                        }
                     }
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::score::Dynamic;
+
+    #[test]
+    fn fortissimo_lasers_outdo_pianissimo_ones() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .init_asset::<Mesh>()
+            .init_asset::<ColorMaterial>()
+            .insert_resource(LaserSFX {
+                fire: Handle::default(),
+            });
+        let [pp, ff] = app
+            .world_mut()
+            .run_system_once(
+                |mut meshes: ResMut<Assets<Mesh>>,
+                 mut materials: ResMut<Assets<ColorMaterial>>,
+                 laser_sfx: Res<LaserSFX>| {
+                    [Dynamic::Pp, Dynamic::Ff].map(|dynamic| {
+                        laser_bundle(
+                            &mut meshes,
+                            &mut materials,
+                            &laser_sfx,
+                            laser_stats(dynamic),
+                            4,
+                            Entity::PLACEHOLDER,
+                            None,
+                        )
+                    })
+                },
+            )
+            .unwrap();
+        assert!(pp.laser.damage_per_beat < ff.laser.damage_per_beat);
+        assert!(pp.laser.length < ff.laser.length);
+    }
+}
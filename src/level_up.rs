@@ -0,0 +1,284 @@
+use bevy::prelude::*;
+use rand::{Rng, seq::IndexedRandom};
+
+use crate::{
+    game_state::{GameState, confirm_pressed},
+    score::{Dynamic, Note, NoteLength, NoteName},
+};
+
+const DRAFT_SIZE: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rarity {
+    Common,
+    Uncommon,
+    Rare,
+    Epic,
+}
+
+impl Rarity {
+    const ALL: [Self; 4] = [Self::Common, Self::Uncommon, Self::Rare, Self::Epic];
+
+    /// Relative chance of rolling this rarity
+    const fn weight(self) -> u32 {
+        match self {
+            Self::Common => 60,
+            Self::Uncommon => 25,
+            Self::Rare => 12,
+            Self::Epic => 3,
+        }
+    }
+
+    const fn lengths(self) -> &'static [NoteLength] {
+        match self {
+            Self::Common => &[NoteLength::Sixteenth, NoteLength::Eighth],
+            Self::Uncommon => &[
+                NoteLength::Eighth,
+                NoteLength::DottedEighth,
+                NoteLength::Quarter,
+            ],
+            Self::Rare => &[
+                NoteLength::Quarter,
+                NoteLength::DottedQuarter,
+                NoteLength::Half,
+            ],
+            Self::Epic => &[NoteLength::Half, NoteLength::DottedHalf, NoteLength::Whole],
+        }
+    }
+
+    const fn dynamics(self) -> &'static [Dynamic] {
+        match self {
+            Self::Common => &[Dynamic::Ppp, Dynamic::Pp, Dynamic::P],
+            Self::Uncommon => &[Dynamic::P, Dynamic::Mp, Dynamic::Mf],
+            Self::Rare => &[Dynamic::Mf, Dynamic::F, Dynamic::Ff],
+            Self::Epic => &[Dynamic::Ff, Dynamic::Fff],
+        }
+    }
+
+    /// Chance of the note rolling a name with an element rather than G
+    const fn element_chance(self) -> f64 {
+        match self {
+            Self::Common => 0.,
+            Self::Uncommon => 0.2,
+            Self::Rare => 0.4,
+            Self::Epic => 0.7,
+        }
+    }
+
+    const fn label(self) -> &'static str {
+        match self {
+            Self::Common => "Common",
+            Self::Uncommon => "Uncommon",
+            Self::Rare => "Rare",
+            Self::Epic => "Epic",
+        }
+    }
+
+    const fn color(self) -> Color {
+        match self {
+            Self::Common => Color::hsva(0., 0., 0.8, 1.),
+            Self::Uncommon => Color::hsva(120., 0.7, 0.9, 1.),
+            Self::Rare => Color::hsva(210., 0.8, 1., 1.),
+            Self::Epic => Color::hsva(280., 0.7, 1., 1.),
+        }
+    }
+}
+
+/// A note offered on level up, along with how rare it was to roll
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DraftedNote {
+    pub note: Note,
+    pub rarity: Rarity,
+}
+
+impl DraftedNote {
+    fn label(self) -> String {
+        format!("{}\n{}", self.note.label(), self.rarity.label())
+    }
+}
+
+pub fn roll_drafted_note(rng: &mut impl Rng) -> DraftedNote {
+    let rarity = *Rarity::ALL
+        .choose_weighted(rng, |rarity| rarity.weight())
+        .unwrap_or(&Rarity::Common);
+    let elemental = rng.random_bool(rarity.element_chance());
+    let names: Vec<NoteName> = NoteName::ALL
+        .into_iter()
+        .filter(|name| name.element().is_some() == elemental)
+        .collect();
+    DraftedNote {
+        note: Note {
+            length: rarity
+                .lengths()
+                .choose(rng)
+                .copied()
+                .unwrap_or(NoteLength::Quarter),
+            dynamic: rarity
+                .dynamics()
+                .choose(rng)
+                .copied()
+                .unwrap_or(Dynamic::Mf),
+            name: names.choose(rng).copied().unwrap_or(NoteName::G),
+        },
+        rarity,
+    }
+}
+
+/// Notes drafted this run, waiting to be placed on a score
+#[derive(Resource, Debug, Default)]
+pub struct NoteInventory(pub Vec<Note>);
+
+/// Levels gained that haven't had their note drafted yet
+#[derive(Resource, Debug, Default)]
+pub struct PendingLevelUps(pub u32);
+
+#[derive(Resource, Debug)]
+pub struct LevelUpDraft {
+    choices: Vec<DraftedNote>,
+    selected: usize,
+}
+
+#[derive(Component)]
+pub struct DraftCard(usize);
+
+#[derive(Component)]
+pub struct DraftCardText(usize);
+
+pub fn reset_level_ups(mut commands: Commands) {
+    commands.insert_resource(NoteInventory::default());
+    commands.insert_resource(PendingLevelUps::default());
+}
+
+#[allow(clippy::needless_pass_by_value)]
+pub fn level_up_system(
+    pending_level_ups: Res<PendingLevelUps>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if pending_level_ups.0 > 0 {
+        next_state.set(GameState::LevelUp);
+    }
+}
+
+/// Rolls the notes to pick from for one pending level
+fn roll_draft(pending_level_ups: &mut PendingLevelUps) -> LevelUpDraft {
+    pending_level_ups.0 = pending_level_ups.0.saturating_sub(1);
+    let mut rng = rand::rng();
+    LevelUpDraft {
+        choices: (0..DRAFT_SIZE)
+            .map(|_| roll_drafted_note(&mut rng))
+            .collect(),
+        selected: 0,
+    }
+}
+
+/// Lays out the cards, filled in by `draft_card_system` from the draft
+pub fn setup_level_up(mut commands: Commands, mut pending_level_ups: ResMut<PendingLevelUps>) {
+    commands.insert_resource(roll_draft(&mut pending_level_ups));
+
+    commands
+        .spawn((
+            DespawnOnExit(GameState::LevelUp),
+            Node {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(24.),
+                ..default()
+            },
+            BackgroundColor(Color::BLACK.with_alpha(0.6)),
+            children![(
+                Text::new("Level up!\nPick a note"),
+                TextFont::from_font_size(32.),
+                TextLayout::new_with_justify(Justify::Center),
+            )],
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(Node {
+                    column_gap: Val::Px(24.),
+                    ..default()
+                })
+                .with_children(|row| {
+                    for index in 0..DRAFT_SIZE {
+                        row.spawn((
+                            DraftCard(index),
+                            Node {
+                                width: Val::Px(180.),
+                                padding: UiRect::all(Val::Px(16.)),
+                                border: UiRect::all(Val::Px(4.)),
+                                justify_content: JustifyContent::Center,
+                                ..default()
+                            },
+                            BorderColor::all(Color::NONE),
+                            BackgroundColor(Color::BLACK.with_alpha(0.8)),
+                            children![(
+                                DraftCardText(index),
+                                Text::default(),
+                                TextFont::from_font_size(24.),
+                                TextColor::default(),
+                                TextLayout::new_with_justify(Justify::Center),
+                            )],
+                        ));
+                    }
+                });
+        });
+}
+
+/// Moves between the cards with left and right, adding the selected note to the inventory on
+/// confirm. Further pending levels are drafted straight away without going back to the song.
+#[allow(clippy::needless_pass_by_value)]
+pub fn level_up_draft_system(
+    input: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    mut draft: ResMut<LevelUpDraft>,
+    mut inventory: ResMut<NoteInventory>,
+    mut pending_level_ups: ResMut<PendingLevelUps>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let pressed = |key: KeyCode, button: GamepadButton| {
+        input.just_pressed(key) || gamepads.iter().any(|gamepad| gamepad.just_pressed(button))
+    };
+    let last = draft.choices.len().saturating_sub(1);
+    if pressed(KeyCode::ArrowLeft, GamepadButton::DPadLeft) {
+        draft.selected = draft.selected.saturating_sub(1);
+    }
+    if pressed(KeyCode::ArrowRight, GamepadButton::DPadRight) {
+        draft.selected = (draft.selected + 1).min(last);
+    }
+    if (confirm_pressed(&input, &gamepads) || pressed(KeyCode::Space, GamepadButton::South))
+        && let Some(choice) = draft.choices.get(draft.selected)
+    {
+        inventory.0.push(choice.note);
+        if pending_level_ups.0 > 0 {
+            *draft = roll_draft(&mut pending_level_ups);
+        } else {
+            next_state.set(GameState::Playing);
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+pub fn draft_card_system(
+    draft: Res<LevelUpDraft>,
+    mut query: Query<(&DraftCard, &mut BorderColor)>,
+    mut text_query: Query<(&DraftCardText, &mut Text, &mut TextColor)>,
+) {
+    if !draft.is_changed() {
+        return;
+    }
+    for (card_text, mut text, mut text_color) in &mut text_query {
+        if let Some(choice) = draft.choices.get(card_text.0) {
+            text.0 = choice.label();
+            text_color.0 = choice.rarity.color();
+        }
+    }
+    for (card, mut border_color) in &mut query {
+        *border_color = if card.0 == draft.selected {
+            BorderColor::all(Color::WHITE)
+        } else {
+            BorderColor::all(Color::NONE)
+        };
+    }
+}
@@ -5,29 +5,44 @@
 mod aoe;
 mod bounce;
 mod bullet;
+mod calibration;
+mod chart;
+mod combo;
 mod enemy;
+mod experience;
 mod follower;
+mod game_state;
 mod health;
 mod instrument;
+mod judgement;
 mod laser;
+mod level_up;
 mod map;
 mod metronome;
 mod nearest_entity;
 mod note_highway;
 mod player;
+mod player_health;
+mod score;
+mod score_editor;
+mod shared_meshes;
 mod slide;
+mod status_effect;
 mod window_size;
 
 use bevy::{
-    asset::AssetMetaCheck, audio::Volume, input::common_conditions::input_toggle_active, log,
-    prelude::*, window::WindowResolution,
+    asset::{AssetMetaCheck, RecursiveDependencyLoadState},
+    input::common_conditions::input_toggle_active,
+    log,
+    prelude::*,
+    window::WindowResolution,
 };
 use bevy_aseprite_ultra::{
     AsepriteUltraPlugin,
     prelude::{Animation, AnimationDirection, AnimationRepeat, AseAnimation},
 };
 use bevy_ecs_tilemap::TilemapPlugin;
-use bevy_egui::EguiPlugin;
+use bevy_egui::{EguiPlugin, EguiPrimaryContextPass};
 use bevy_enhanced_input::prelude::{Press, *};
 use bevy_hotpatching_experiments::prelude::*;
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
@@ -41,32 +56,72 @@ use bevy_rapier2d::{
 use fraction::Fraction;
 
 use crate::{
-    aoe::{aoe_bundle, aoe_collision_system, aoe_system, process_aoe_duration},
+    aoe::{
+        AoeStats, aoe_bundle, aoe_collision_system, aoe_pulse_system, aoe_stats, aoe_system,
+        on_aoe_add, process_aoe_duration,
+    },
     bounce::{bounce_system, initial_bounce, tile_bounce_system},
     bullet::{
-        bullet_collision_system, bullet_launcher_bundle, bullet_launcher_system, bullet_system,
-        setup_bullet_sfx,
+        BulletStats, bullet_collision_system, bullet_launcher_bundle, bullet_launcher_system,
+        bullet_stats, bullet_system, explosion_system, setup_bullet_sfx,
+    },
+    calibration::{
+        Calibration, calibration_display_system, enter_calibration, exit_calibration,
+        record_calibration_tap, saved_latency_offsets, setup_latency_offsets, skip_calibration,
     },
+    chart::{
+        Chart, ChartLoader, SongChart, chart_metronome, section_text_system, setup_section_text,
+    },
+    combo::{combo_system, combo_text_system, reset_combo, setup_combo_text},
     enemy::{
-        Enemy, EnemySpawnTimer, raccoon_bullet_collision_system, raccoon_bullet_system,
-        raccoon_movement_system, skunk_movement_system, spawn_raccoon_system, spawn_skunk_system,
+        Enemy, raccoon_bullet_collision_system, raccoon_bullet_system, raccoon_movement_system,
+        skunk_movement_system, spawn_raccoon_system, spawn_skunk_system,
+    },
+    experience::{
+        Magnet, reset_experience, setup_xp_bar, xp_bar_system, xp_gem_collision_system,
+        xp_gem_magnet_system,
     },
     follower::{Follower, follower_system},
-    health::{despawn_enemy_on_zero_health, health_bar_system, on_health_bar_add},
+    game_state::{
+        GameState, InRun, countdown_system, end_run, end_screen_system, main_menu_system,
+        resume_run, setup_game_over_screen, setup_main_menu, setup_pause_screen,
+        setup_victory_screen, start_countdown, start_run, suspend_run, victory_system,
+    },
+    health::{
+        DamageEvent, EnemyKilled, Health, PlayerHit, damage_system, despawn_killed_enemies,
+        health_bar_system, on_health_bar_add,
+    },
     instrument::{Tuba, Violin, spawn_tuba, spawn_violin},
-    laser::{LaserSFX, laser_bundle, laser_system, setup_laser_sfx},
+    judgement::{
+        Judge, JudgedInput, JudgedNotes, Judgement, JudgementTier, JudgementWindows,
+        judgement_text_system, miss_lockout_system, passive_miss_system, reset_judged_notes,
+        setup_judgement_text,
+    },
+    laser::{LaserSFX, LaserStats, laser_bundle, laser_stats, laser_system, setup_laser_sfx},
+    level_up::{
+        draft_card_system, level_up_draft_system, level_up_system, reset_level_ups, setup_level_up,
+    },
     map::setup_map,
-    metronome::{Metronome, down_beats, initial_metronome, metronome_system, within_nanos_window},
+    metronome::{BeatCrossed, Metronome, down_beats, metronome_system, steps_per_beat_unit},
     note_highway::{
-        beat_line_system, note_highway_system, on_beat_line_system, setup_note_highway,
+        HighwaySettings, beat_line_system, gem_effect_system, note_gem_judgement_system,
+        note_gem_sync_system, note_gem_system, note_highway_system, on_beat_line_system,
+        setup_note_highway,
     },
     player::Player,
+    player_health::{
+        enemy_contact_system, invulnerability_system, player_death_system,
+        player_health_bar_system, player_hit_system, setup_player_health_bar,
+    },
+    score::{Dynamic, Note, NoteLength, NoteName, Score, starting_score},
+    score_editor::{score_editor_keyboard_system, score_editor_ui_system, setup_score_editor},
+    shared_meshes::setup_shared_meshes,
     slide::{Slide, initial_slide, slide_system},
+    status_effect::{ApplyStatusEffect, apply_status_effect_system, status_effect_system},
     window_size::{WINDOW_HEIGHT, WINDOW_WIDTH, setup_window_size},
 };
 
-const SONG_BPM: u64 = 85;
-const SONG_FILE: &str = "sounds/clicktrack-85bpm.ogg";
+const SONG_CHART: &str = "charts/clicktrack-85bpm.chart.ron";
 
 fn main() {
     App::new()
@@ -97,25 +152,143 @@ fn main() {
         .add_plugins(SimpleSubsecondPlugin::default())
         .add_input_context::<Player>()
         .add_input_context::<Song>()
+        .add_input_context::<Calibration>()
+        .add_message::<BeatCrossed>()
+        .add_message::<Judgement>()
+        .add_message::<DamageEvent>()
+        .add_message::<EnemyKilled>()
+        .add_message::<PlayerHit>()
+        .add_message::<ApplyStatusEffect>()
+        .init_resource::<HighwaySettings>()
+        .init_resource::<JudgedNotes>()
+        .init_asset::<Chart>()
+        .init_asset_loader::<ChartLoader>()
+        .init_state::<GameState>()
+        .add_computed_state::<InRun>()
         .add_systems(
             Startup,
             (
                 setup_window_size,
+                setup_shared_meshes,
                 setup,
+                setup_latency_offsets,
                 setup_map,
                 set_gravity,
-                setup_note_highway,
                 setup_laser_sfx,
                 setup_bullet_sfx,
             )
                 .chain(),
         )
-        .add_systems(First, metronome_system)
         .add_systems(
             Update,
-            (destroy_all_enemies, spawn_new_violin, spawn_new_tuba),
+            finish_loading_when_chart_loaded.run_if(in_state(GameState::Loading)),
         )
-        .add_systems(Update, follower_system)
+        .add_systems(OnEnter(GameState::Calibrating), enter_calibration)
+        .add_systems(OnExit(GameState::Calibrating), exit_calibration)
+        .add_systems(OnEnter(GameState::MainMenu), setup_main_menu)
+        .add_systems(
+            Update,
+            // The key that skipped calibration is still pressed on the frame the menu opens
+            main_menu_system
+                .run_if(in_state(GameState::MainMenu).and(not(state_changed::<GameState>))),
+        )
+        .add_systems(
+            OnEnter(GameState::Countdown),
+            (
+                (
+                    start_run,
+                    reset_level_ups,
+                    reset_experience,
+                    reset_combo,
+                    reset_judged_notes,
+                ),
+                (
+                    setup_player,
+                    (
+                        setup_note_highway,
+                        (setup_judgement_text, setup_combo_text, setup_section_text),
+                    )
+                        .chain(),
+                    setup_xp_bar,
+                    setup_player_health_bar,
+                ),
+                start_countdown,
+            )
+                .chain(),
+        )
+        .add_systems(
+            Update,
+            countdown_system.run_if(in_state(GameState::Countdown)),
+        )
+        .add_systems(OnEnter(GameState::Playing), resume_run)
+        .add_systems(OnExit(GameState::Playing), suspend_run)
+        .add_systems(OnEnter(GameState::LevelUp), setup_level_up)
+        .add_systems(
+            Update,
+            (level_up_draft_system, draft_card_system)
+                .chain()
+                .run_if(in_state(GameState::LevelUp)),
+        )
+        .add_systems(OnEnter(GameState::EditingScore), setup_score_editor)
+        .add_systems(
+            EguiPrimaryContextPass,
+            score_editor_ui_system.run_if(in_state(GameState::EditingScore)),
+        )
+        .add_systems(
+            Update,
+            score_editor_keyboard_system.run_if(in_state(GameState::EditingScore)),
+        )
+        .add_systems(OnEnter(GameState::Paused), setup_pause_screen)
+        .add_systems(OnEnter(GameState::Victory), setup_victory_screen)
+        .add_systems(OnEnter(GameState::GameOver), setup_game_over_screen)
+        .add_systems(
+            Update,
+            end_screen_system
+                .run_if(in_state(GameState::Victory).or(in_state(GameState::GameOver))),
+        )
+        .add_systems(OnExit(InRun), end_run)
+        .add_systems(
+            First,
+            metronome_system
+                .run_if(in_state(GameState::Playing).or(in_state(GameState::Calibrating))),
+        )
+        .add_systems(
+            Update,
+            calibration_display_system.run_if(in_state(GameState::Calibrating)),
+        )
+        .add_systems(
+            Update,
+            (
+                destroy_all_enemies,
+                spawn_new_violin,
+                spawn_new_tuba,
+                victory_system,
+                xp_gem_magnet_system,
+                xp_gem_collision_system,
+                xp_bar_system,
+                level_up_system,
+            )
+                .run_if(in_state(GameState::Playing)),
+        )
+        .add_systems(
+            Update,
+            (
+                enemy_contact_system,
+                (apply_status_effect_system, status_effect_system).chain(),
+                damage_system,
+                (despawn_killed_enemies, player_hit_system),
+                invulnerability_system,
+                player_health_bar_system,
+                player_death_system,
+            )
+                .chain()
+                .after(raccoon_bullet_collision_system)
+                .after(bullet_collision_system)
+                .after(laser_system)
+                .after(aoe_pulse_system)
+                .run_if(in_state(GameState::Playing)),
+        )
+        .add_systems(Update, follower_system.run_if(in_state(GameState::Playing)))
         .add_systems(
             Update,
             (
@@ -123,7 +296,8 @@ fn main() {
                 raccoon_movement_system,
                 raccoon_bullet_collision_system,
                 raccoon_bullet_system,
-            ),
+            )
+                .run_if(in_state(GameState::Playing)),
         )
         .add_systems(
             Update,
@@ -131,7 +305,8 @@ fn main() {
                 spawn_skunk_system,
                 spawn_skunk_system,
                 skunk_movement_system,
-            ),
+            )
+                .run_if(in_state(GameState::Playing)),
         )
         .add_systems(
             Update,
@@ -141,28 +316,51 @@ fn main() {
                 beat_line_system,
                 tile_bounce_system,
                 bounce_system,
-                aoe_system,
+                (aoe_system, aoe_pulse_system).chain(),
                 aoe_collision_system,
                 process_aoe_duration,
                 bullet_system,
                 bullet_launcher_system,
+                explosion_system,
                 laser_system,
-                despawn_enemy_on_zero_health,
                 health_bar_system,
                 bullet_collision_system,
                 slide_system,
                 player_animation,
-            ),
+                (
+                    miss_lockout_system,
+                    passive_miss_system,
+                    judgement_text_system,
+                    combo_system,
+                    combo_text_system,
+                    section_text_system,
+                ),
+            )
+                .run_if(in_state(GameState::Playing)),
+        )
+        .add_systems(
+            Update,
+            (note_gem_sync_system, note_gem_system)
+                .chain()
+                .run_if(in_state(GameState::Playing).or(in_state(GameState::EditingScore))),
+        )
+        .add_systems(
+            Update,
+            (note_gem_judgement_system, gem_effect_system).run_if(in_state(GameState::Playing)),
         )
         .add_observer(on_health_bar_add)
+        .add_observer(on_aoe_add)
         .add_observer(apply_movement)
         .add_observer(toggle_audio)
         .add_observer(toggle_muted)
+        .add_observer(toggle_score_editor)
         .add_observer(apply_slide)
         .add_observer(apply_north_note_played)
         .add_observer(apply_east_note_played)
         .add_observer(apply_south_note_played)
         .add_observer(apply_west_note_played)
+        .add_observer(record_calibration_tap)
+        .add_observer(skip_calibration)
         .run();
 }
 
@@ -176,16 +374,13 @@ fn set_gravity(rapier_config: Query<&mut RapierConfiguration>) {
     }
 }
 
-#[derive(Resource)]
-struct GracePeriod(Fraction);
-
 #[allow(clippy::needless_pass_by_value)]
 fn setup(asset_server: Res<AssetServer>, mut commands: Commands) {
-    commands.insert_resource(initial_metronome(SONG_BPM));
-    commands.insert_resource(GracePeriod(Fraction::from(90u64 * 1_000_000)));
-    commands.insert_resource(EnemySpawnTimer {
-        skunk_timer: Timer::from_seconds(2., TimerMode::Repeating),
-        raccoon_timer: Timer::from_seconds(3.5, TimerMode::Repeating),
+    commands.insert_resource(SongChart(asset_server.load(SONG_CHART)));
+    commands.insert_resource(JudgementWindows {
+        perfect: Fraction::from(25u64 * 1_000_000),
+        great: Fraction::from(50u64 * 1_000_000),
+        good: Fraction::from(90u64 * 1_000_000),
     });
     commands.spawn((
         Camera2d,
@@ -194,9 +389,43 @@ fn setup(asset_server: Res<AssetServer>, mut commands: Commands) {
             ..OrthographicProjection::default_2d()
         }),
     ));
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn finish_loading_when_chart_loaded(
+    asset_server: Res<AssetServer>,
+    charts: Res<Assets<Chart>>,
+    song_chart: Res<SongChart>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if let RecursiveDependencyLoadState::Failed(error) =
+        asset_server.recursive_dependency_load_state(&song_chart.0)
+    {
+        log::error!("Failed to load song chart {SONG_CHART}: {error}");
+        return;
+    }
+    if !asset_server.is_loaded_with_dependencies(&song_chart.0) {
+        return;
+    }
+    if !charts.contains(&song_chart.0) {
+        return;
+    }
+
+    next_state.set(if saved_latency_offsets().is_some() {
+        GameState::MainMenu
+    } else {
+        GameState::Calibrating
+    });
+}
+
+/// Spawns the chart's song from the top, with a metronome following it
+pub fn spawn_song(commands: &mut Commands, chart: &Chart, playback_settings: PlaybackSettings) {
+    let mut metronome = chart_metronome(chart);
+    metronome.started = !playback_settings.paused;
+    commands.insert_resource(metronome);
     commands.spawn((
-        AudioPlayer::new(asset_server.load::<AudioSource>(SONG_FILE)),
-        PlaybackSettings::default().paused(),
+        AudioPlayer::new(chart.audio.clone()),
+        playback_settings,
         Song,
         actions!(Song[(
             Action::<ToggleAudio>::new(),
@@ -206,13 +435,21 @@ fn setup(asset_server: Res<AssetServer>, mut commands: Commands) {
             Action::<ToggleMuted>::new(),
             Press::default(),
             bindings![KeyCode::KeyZ, GamepadButton::Select],
+        ),(
+            Action::<ToggleScoreEditor>::new(),
+            Press::default(),
+            bindings![KeyCode::Tab],
         )]),
     ));
+}
 
+#[allow(clippy::needless_pass_by_value)]
+fn setup_player(asset_server: Res<AssetServer>, metronome: Res<Metronome>, mut commands: Commands) {
     let player_sprite_scale = 0.15;
     let mut sprite_transform = Transform::from_xyz(0., 0., 1.);
     sprite_transform.scale = Vec3::new(player_sprite_scale, player_sprite_scale, 0.);
     commands.spawn((
+        DespawnOnExit(InRun),
         Transform::from_xyz(0., 0., 2.),
         RigidBody::KinematicVelocityBased,
         KinematicCharacterController {
@@ -223,7 +460,29 @@ fn setup(asset_server: Res<AssetServer>, mut commands: Commands) {
         Collider::capsule_y(100. * player_sprite_scale, 25. * player_sprite_scale),
         ActiveCollisionTypes::KINEMATIC_KINEMATIC,
         MovementSpeed(0.5),
-        Player,
+        (
+            Player,
+            Magnet {
+                radius: 60.,
+                speed: 200.,
+            },
+            Health {
+                max_health: 10,
+                current_health: 10,
+            },
+        ),
+        ContextActivity::<Player>::INACTIVE,
+        starting_score(
+            &metronome,
+            NotePlayed::SouthNote,
+            Note {
+                length: NoteLength::Eighth,
+                dynamic: Dynamic::Mf,
+                name: NoteName::G,
+            },
+            0,
+            4,
+        ),
         Velocity::zero(),
         Visibility::default(),
         children![(
@@ -271,7 +530,7 @@ fn setup(asset_server: Res<AssetServer>, mut commands: Commands) {
 }
 
 #[derive(Component)]
-struct Song;
+pub struct Song;
 
 #[derive(InputAction)]
 #[action_output(bool)]
@@ -281,21 +540,33 @@ struct ToggleAudio;
 #[action_output(bool)]
 struct ToggleMuted;
 
+#[derive(InputAction)]
+#[action_output(bool)]
+struct ToggleScoreEditor;
+
 #[allow(clippy::needless_pass_by_value)]
 fn toggle_audio(
     _toggle_audio: On<Fire<ToggleAudio>>,
-    mut audio_sink: Query<&mut AudioSink, With<Song>>,
-    mut metronome: ResMut<Metronome>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
 ) {
-    if let Ok(mut audio_sink) = audio_sink.single_mut() {
-        if metronome.started {
-            audio_sink.pause();
-            metronome.started = false;
-        } else {
-            audio_sink.set_volume(Volume::Linear(1.));
-            audio_sink.play();
-            metronome.started = true;
-        }
+    match state.get() {
+        GameState::Playing => next_state.set(GameState::Paused),
+        GameState::Paused => next_state.set(GameState::Playing),
+        _ => {}
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn toggle_score_editor(
+    _toggle_score_editor: On<Fire<ToggleScoreEditor>>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    match state.get() {
+        GameState::Playing => next_state.set(GameState::EditingScore),
+        GameState::EditingScore => next_state.set(GameState::Playing),
+        _ => {}
     }
 }
 
@@ -303,17 +574,12 @@ fn toggle_audio(
 fn toggle_muted(
     _toggle_muted: On<Fire<ToggleMuted>>,
     mut audio_sink: Query<&mut AudioSink, With<Song>>,
-    mut metronome: ResMut<Metronome>,
+    state: Res<State<GameState>>,
 ) {
-    if let Ok(mut audio_sink) = audio_sink.single_mut() {
-        if metronome.started {
-            audio_sink.pause();
-            metronome.started = false;
-        } else {
-            audio_sink.set_volume(Volume::SILENT);
-            audio_sink.play();
-            metronome.started = true;
-        }
+    if matches!(state.get(), GameState::Playing | GameState::Paused)
+        && let Ok(mut audio_sink) = audio_sink.single_mut()
+    {
+        audio_sink.toggle_mute();
     }
 }
 
@@ -333,6 +599,7 @@ fn destroy_all_enemies(
 #[allow(clippy::needless_pass_by_value)]
 fn spawn_new_violin(
     input: Res<ButtonInput<KeyCode>>,
+    metronome: Res<Metronome>,
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     player_query: Query<(Entity, &Transform), With<Player>>,
@@ -348,6 +615,17 @@ fn spawn_new_violin(
                 player_entity,
                 30.,
                 player_transform.translation.xy(),
+                starting_score(
+                    &metronome,
+                    NotePlayed::NorthNote,
+                    Note {
+                        length: NoteLength::Quarter,
+                        dynamic: Dynamic::Mf,
+                        name: NoteName::G,
+                    },
+                    0,
+                    2,
+                ),
             );
         } else {
             let last_follower = follower_query.iter().last();
@@ -358,6 +636,17 @@ fn spawn_new_violin(
                     last_follower_entity,
                     30.,
                     last_follower_transform.translation.xy(),
+                    starting_score(
+                        &metronome,
+                        NotePlayed::NorthNote,
+                        Note {
+                            length: NoteLength::Quarter,
+                            dynamic: Dynamic::Mf,
+                            name: NoteName::G,
+                        },
+                        0,
+                        2,
+                    ),
                 );
             }
         }
@@ -367,6 +656,7 @@ fn spawn_new_violin(
 #[allow(clippy::needless_pass_by_value)]
 fn spawn_new_tuba(
     input: Res<ButtonInput<KeyCode>>,
+    metronome: Res<Metronome>,
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     player_query: Query<(Entity, &Transform), With<Player>>,
@@ -382,6 +672,17 @@ fn spawn_new_tuba(
                 player_entity,
                 30.,
                 player_transform.translation.xy(),
+                starting_score(
+                    &metronome,
+                    NotePlayed::EastNote,
+                    Note {
+                        length: NoteLength::Quarter,
+                        dynamic: Dynamic::Mf,
+                        name: NoteName::G,
+                    },
+                    1,
+                    2,
+                ),
             );
         } else {
             let last_follower = follower_query.iter().last();
@@ -392,6 +693,17 @@ fn spawn_new_tuba(
                     last_follower_entity,
                     30.,
                     last_follower_transform.translation.xy(),
+                    starting_score(
+                        &metronome,
+                        NotePlayed::EastNote,
+                        Note {
+                            length: NoteLength::Quarter,
+                            dynamic: Dynamic::Mf,
+                            name: NoteName::G,
+                        },
+                        1,
+                        2,
+                    ),
                 );
             }
         }
@@ -465,25 +777,28 @@ fn apply_slide(
     mut commands: Commands,
     query: Query<&KinematicCharacterController>,
     metronome: Res<Metronome>,
-    grace_period: Res<GracePeriod>,
+    mut judge: Judge,
 ) {
     if let Ok(kinematic_character_controller) = query.get(slide_input_action.context)
         && let Some(velocity) = kinematic_character_controller.translation
-        && down_beats()
-            .iter()
-            .any(|&beat| within_nanos_window(&metronome, beat, grace_period.0))
+        && let Some(judgement) = judge.judge(
+            slide_input_action.context,
+            JudgedInput::Slide,
+            down_beats(&metronome),
+        )
+        && judgement.tier != JudgementTier::Miss
     {
         commands
             .entity(slide_input_action.context)
             .insert(initial_slide(
-                10.,
+                10. * judgement.tier.power_multiplier(),
                 velocity.normalize_or_zero(),
                 1,
-                &metronome,
             ));
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum NotePlayed {
     NorthNote,
     EastNote,
@@ -516,9 +831,10 @@ fn apply_north_note_played(
     commands: Commands,
     metronome: Res<Metronome>,
     laser_sfx: Res<LaserSFX>,
-    grace_period: Res<GracePeriod>,
-    violin_query: Query<Entity, With<Violin>>,
-    tuba_query: Query<Entity, With<Tuba>>,
+    judge: Judge,
+    violin_query: Query<(Entity, &Score), With<Violin>>,
+    tuba_query: Query<(Entity, &Score), With<Tuba>>,
+    player_query: Query<&Score, With<Player>>,
 ) {
     apply_note_played(
         meshes,
@@ -528,9 +844,10 @@ fn apply_north_note_played(
         commands,
         metronome,
         laser_sfx,
-        grace_period,
+        judge,
         violin_query,
         tuba_query,
+        player_query,
     );
 }
 
@@ -543,9 +860,10 @@ fn apply_east_note_played(
     commands: Commands,
     metronome: Res<Metronome>,
     laser_sfx: Res<LaserSFX>,
-    grace_period: Res<GracePeriod>,
-    violin_query: Query<Entity, With<Violin>>,
-    tuba_query: Query<Entity, With<Tuba>>,
+    judge: Judge,
+    violin_query: Query<(Entity, &Score), With<Violin>>,
+    tuba_query: Query<(Entity, &Score), With<Tuba>>,
+    player_query: Query<&Score, With<Player>>,
 ) {
     apply_note_played(
         meshes,
@@ -555,9 +873,10 @@ fn apply_east_note_played(
         commands,
         metronome,
         laser_sfx,
-        grace_period,
+        judge,
         violin_query,
         tuba_query,
+        player_query,
     );
 }
 
@@ -570,9 +889,10 @@ fn apply_south_note_played(
     commands: Commands,
     metronome: Res<Metronome>,
     laser_sfx: Res<LaserSFX>,
-    grace_period: Res<GracePeriod>,
-    violin_query: Query<Entity, With<Violin>>,
-    tuba_query: Query<Entity, With<Tuba>>,
+    judge: Judge,
+    violin_query: Query<(Entity, &Score), With<Violin>>,
+    tuba_query: Query<(Entity, &Score), With<Tuba>>,
+    player_query: Query<&Score, With<Player>>,
 ) {
     apply_note_played(
         meshes,
@@ -582,9 +902,10 @@ fn apply_south_note_played(
         commands,
         metronome,
         laser_sfx,
-        grace_period,
+        judge,
         violin_query,
         tuba_query,
+        player_query,
     );
 }
 
@@ -597,9 +918,10 @@ fn apply_west_note_played(
     commands: Commands,
     metronome: Res<Metronome>,
     laser_sfx: Res<LaserSFX>,
-    grace_period: Res<GracePeriod>,
-    violin_query: Query<Entity, With<Violin>>,
-    tuba_query: Query<Entity, With<Tuba>>,
+    judge: Judge,
+    violin_query: Query<(Entity, &Score), With<Violin>>,
+    tuba_query: Query<(Entity, &Score), With<Tuba>>,
+    player_query: Query<&Score, With<Player>>,
 ) {
     apply_note_played(
         meshes,
@@ -609,12 +931,23 @@ fn apply_west_note_played(
         commands,
         metronome,
         laser_sfx,
-        grace_period,
+        judge,
         violin_query,
         tuba_query,
+        player_query,
     );
 }
 
+/// Damage scaled by how well the note was played, never dropping to nothing
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+fn powered(damage: u128, power: f32) -> u128 {
+    ((damage as f32 * power).round() as u128).max(1)
+}
+
 #[allow(clippy::needless_pass_by_value)]
 #[allow(clippy::too_many_arguments)]
 fn apply_note_played(
@@ -625,42 +958,83 @@ fn apply_note_played(
     mut commands: Commands,
     metronome: Res<Metronome>,
     laser_sfx: Res<LaserSFX>,
-    grace_period: Res<GracePeriod>,
-    violin_query: Query<Entity, With<Violin>>,
-    tuba_query: Query<Entity, With<Tuba>>,
+    mut judge: Judge,
+    violin_query: Query<(Entity, &Score), With<Violin>>,
+    tuba_query: Query<(Entity, &Score), With<Tuba>>,
+    player_query: Query<&Score, With<Player>>,
 ) {
-    if down_beats()
+    let note_in_lane =
+        |score: &Score, beat: u8| score.note_at(beat).filter(|_| score.lane() == note_played);
+    let mut expected_beats: Vec<u8> = violin_query
         .iter()
-        .any(|&beat| within_nanos_window(&metronome, beat, grace_period.0))
+        .chain(&tuba_query)
+        .map(|(_, score)| score)
+        .chain(player_query.get(player_entity))
+        .flat_map(|score| score.beats_in_lane(note_played))
+        .collect();
+    expected_beats.sort_unstable();
+    expected_beats.dedup();
+
+    if let Some(judgement) = judge.judge(
+        player_entity,
+        JudgedInput::Note(note_played),
+        expected_beats,
+    ) && judgement.tier != JudgementTier::Miss
     {
-        match note_played {
-            NotePlayed::NorthNote => {
-                for violin_entity in violin_query.iter() {
-                    commands.spawn(laser_bundle(
+        let power = judgement.tier.power_multiplier();
+        for (violin_entity, score) in &violin_query {
+            if let Some(note) = note_in_lane(score, judgement.beat) {
+                let stats = laser_stats(note.dynamic);
+                commands.spawn((
+                    DespawnOnExit(InRun),
+                    laser_bundle(
                         &mut meshes,
                         &mut materials,
                         &laser_sfx,
-                        1,
-                        4,
-                        10.,
-                        500.,
+                        LaserStats {
+                            damage_per_beat: powered(stats.damage_per_beat, power),
+                            width: stats.width * power,
+                            length: stats.length * power,
+                        },
+                        note.length.steps(&metronome),
                         violin_entity,
-                    ));
-                }
+                        note.element(),
+                    ),
+                ));
             }
-            NotePlayed::EastNote => {
-                for tuba_entity in tuba_query.iter() {
-                    commands
-                        .entity(tuba_entity)
-                        .with_child(bullet_launcher_bundle(3.0, 150.0, 2, 4));
-                }
-            }
-            NotePlayed::SouthNote => {
+        }
+        for (tuba_entity, score) in &tuba_query {
+            if let Some(note) = note_in_lane(score, judgement.beat) {
+                let stats = bullet_stats(note.dynamic);
                 commands
-                    .entity(player_entity)
-                    .with_child(aoe_bundle(&metronome, 30.0, 75.0, 2));
+                    .entity(tuba_entity)
+                    .with_child(bullet_launcher_bundle(
+                        3.0,
+                        150.0,
+                        BulletStats {
+                            damage: powered(stats.damage, power),
+                            ..stats
+                        },
+                        note.length.steps(&metronome),
+                        note.element(),
+                    ));
             }
-            NotePlayed::WestNote => {}
+        }
+        if let Ok(score) = player_query.get(player_entity)
+            && let Some(note) = note_in_lane(score, judgement.beat)
+        {
+            let stats = aoe_stats(note.dynamic);
+            commands.entity(player_entity).with_child(aoe_bundle(
+                AoeStats {
+                    initial_radius: stats.initial_radius * power,
+                    final_radius: stats.final_radius * power,
+                    damage_per_pulse: powered(stats.damage_per_pulse, power),
+                    ..stats
+                },
+                note.length.steps(&metronome),
+                steps_per_beat_unit(&metronome),
+                note.element(),
+            ));
         }
     }
 }
@@ -1,106 +1,482 @@
+use std::time::Duration;
+
 use bevy::prelude::*;
 use fraction::Fraction;
+use serde::Deserialize;
+
+use crate::Song;
+
+/// Drift past which the conductor jumps straight to the song position instead of easing towards it
+const DRIFT_SNAP_NANOS: i64 = 100_000_000;
+/// Only this fraction (1/n) of the measured drift is corrected each frame to smooth out jitter
+const DRIFT_CORRECTION_DIVISOR: i64 = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub struct TimeSignature {
+    pub beats_per_measure: u8,
+    pub beat_unit: u8,
+}
+
+impl TimeSignature {
+    /// Compound meters (6/8, 9/8, 12/8) are felt in groups of three beat units
+    const fn is_compound(self) -> bool {
+        self.beat_unit == 8
+            && self.beats_per_measure > 3
+            && self.beats_per_measure.is_multiple_of(3)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum Subdivision {
+    Sixteenths,
+    Triplets,
+}
+
+impl Subdivision {
+    /// Number of metronome steps each beat unit of the time signature is split into
+    pub const fn steps_per_beat_unit(self, time_signature: TimeSignature) -> u8 {
+        match self {
+            Self::Sixteenths => {
+                if time_signature.beat_unit >= 16 {
+                    1
+                } else {
+                    16 / time_signature.beat_unit
+                }
+            }
+            Self::Triplets => 3,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum TempoTransition {
+    Instant,
+    /// Accelerando or ritardando reaching the new tempo after this many steps
+    Ramp {
+        beats: u64,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub struct TempoChange {
+    /// Metronome step, counted from the start of the song, at which the change begins
+    pub beat: u64,
+    pub bpm: u64,
+    pub transition: TempoTransition,
+}
+
+impl TempoChange {
+    /// Tempo this change sets on `beat`, given the tempo in effect before it started
+    const fn bpm_at(&self, from_bpm: u64, beat: u64) -> u64 {
+        match self.transition {
+            TempoTransition::Ramp { beats } if beat - self.beat < beats => {
+                #[allow(clippy::cast_possible_wrap)]
+                let bpm_diff = self.bpm as i64 - from_bpm as i64;
+                #[allow(clippy::cast_possible_wrap)]
+                let progress = (beat - self.beat) as i64;
+                #[allow(clippy::cast_possible_wrap)]
+                let beats = beats as i64;
+                from_bpm.saturating_add_signed(bpm_diff * progress / beats)
+            }
+            _ => self.bpm,
+        }
+    }
+}
+
+const NANOS_PER_MINUTE: u64 = 60_000_000_000;
+
+/// Nanoseconds `steps` at `bpm` would last if each step were a whole beat unit
+fn beat_unit_nanos(steps: u64, bpm: u64) -> u64 {
+    (u128::from(steps) * u128::from(NANOS_PER_MINUTE) / u128::from(bpm))
+        .try_into()
+        .unwrap_or(u64::MAX)
+}
+
+/// Steps from `start` up to the next span, all lasting the same
+#[derive(Clone, Copy, Debug)]
+struct TempoSpan {
+    start: u64,
+    bpm: u64,
+    /// Nanoseconds from the start of the song until `start`, multiplied by the steps per beat
+    /// unit so it holds for any subdivision
+    beat_unit_nanos: u64,
+}
+
+/// Tempo over the whole song. Ramps change tempo step by step, so every
+/// metronome step has a constant length.
+#[derive(Clone, Debug)]
+pub struct TempoMap {
+    initial_bpm: u64,
+    changes: Vec<TempoChange>,
+    /// Precomputed from the changes whenever one is added, ordered by `start`
+    spans: Vec<TempoSpan>,
+}
+
+impl TempoMap {
+    pub fn new(initial_bpm: u64) -> Self {
+        Self {
+            initial_bpm,
+            changes: Vec::new(),
+            spans: vec![TempoSpan {
+                start: 0,
+                bpm: initial_bpm,
+                beat_unit_nanos: 0,
+            }],
+        }
+    }
+
+    pub fn with_change(mut self, change: TempoChange) -> Self {
+        let index = self.changes.partition_point(|c| c.beat <= change.beat);
+        self.changes.insert(index, change);
+        self.spans = self.build_spans();
+        self
+    }
+
+    /// Tempo on `beat` worked out from the changes, rather than the precomputed spans
+    fn changed_bpm_at(&self, beat: u64) -> u64 {
+        let mut bpm = self.initial_bpm;
+        let mut current: Option<(&TempoChange, u64)> = None;
+        for change in self.changes.iter().take_while(|change| change.beat <= beat) {
+            let from_bpm = current.map_or(bpm, |(previous, from_bpm)| {
+                previous.bpm_at(from_bpm, change.beat)
+            });
+            current = Some((change, from_bpm));
+        }
+        if let Some((change, from_bpm)) = current {
+            bpm = change.bpm_at(from_bpm, beat);
+        }
+        bpm
+    }
+
+    /// Step at which the tempo in effect on `beat` may next change
+    fn next_tempo_change(&self, beat: u64) -> u64 {
+        let in_ramp = self.changes.iter().any(|change| {
+            matches!(change.transition, TempoTransition::Ramp { beats }
+                if change.beat <= beat && beat - change.beat < beats)
+        });
+        if in_ramp {
+            beat + 1
+        } else {
+            self.changes
+                .iter()
+                .map(|change| change.beat)
+                .find(|&change_beat| change_beat > beat)
+                .unwrap_or(u64::MAX)
+        }
+    }
+
+    fn build_spans(&self) -> Vec<TempoSpan> {
+        let mut spans = Vec::new();
+        let mut start = 0;
+        let mut beat_unit_nanos_so_far = 0;
+        loop {
+            let bpm = self.changed_bpm_at(start);
+            spans.push(TempoSpan {
+                start,
+                bpm,
+                beat_unit_nanos: beat_unit_nanos_so_far,
+            });
+            let end = self.next_tempo_change(start);
+            if end == u64::MAX {
+                return spans;
+            }
+            beat_unit_nanos_so_far += beat_unit_nanos(end - start, bpm);
+            start = end;
+        }
+    }
 
+    /// Span `beat` falls in
+    fn span_at(&self, beat: u64) -> TempoSpan {
+        let index = self.spans.partition_point(|span| span.start <= beat);
+        self.spans[index.saturating_sub(1)]
+    }
+
+    pub fn bpm_at(&self, beat: u64) -> u64 {
+        self.span_at(beat).bpm
+    }
+
+    /// Nanoseconds from the start of the song until `beat` starts
+    pub fn nanos_at_beat(&self, beat: u64, steps_per_beat_unit: u8) -> u64 {
+        let span = self.span_at(beat);
+        span.beat_unit_nanos
+            .saturating_add(beat_unit_nanos(beat - span.start, span.bpm))
+            / u64::from(steps_per_beat_unit)
+    }
+
+    /// Number of steps started by `nanos` into the song
+    pub fn beat_at_nanos(&self, nanos: u64, steps_per_beat_unit: u8) -> u64 {
+        // Largest beat unit nanos still landing on or before `nanos` once divided into steps
+        let limit = nanos
+            .saturating_add(1)
+            .saturating_mul(u64::from(steps_per_beat_unit))
+            - 1;
+        let index = self
+            .spans
+            .partition_point(|span| span.beat_unit_nanos <= limit);
+        let span = self.spans[index.saturating_sub(1)];
+        let into_span = u128::from(limit - span.beat_unit_nanos);
+        let steps = ((into_span + 1) * u128::from(span.bpm) - 1) / u128::from(NANOS_PER_MINUTE);
+        span.start
+            .saturating_add(steps.try_into().unwrap_or(u64::MAX))
+    }
+}
+
+fn nanos_fraction_per_step(bpm: u64, steps_per_beat_unit: u8) -> Fraction {
+    Fraction::new(NANOS_PER_MINUTE, bpm * u64::from(steps_per_beat_unit))
+}
+
+/// `beat` counts metronome steps (subdivisions) within the current measure, not beat units
 #[derive(Resource, Clone)]
 pub struct Metronome {
     pub beat: u8,
+    /// Steps started since the beginning of the song
+    pub total_beats: u64,
+    /// Current beat units per minute, e.g. quarter notes in 4/4 or eighth notes in 7/8
     pub bpm: u64,
+    pub tempo_map: TempoMap,
+    pub time_signature: TimeSignature,
+    pub subdivision: Subdivision,
     pub is_beat_start_frame: bool,
     pub nanos_accumulated: Fraction,
     pub started: bool,
+    /// Conductor clock, the smoothed playback position of the song in nanoseconds
+    pub song_position_nanos: u64,
+    /// Time into the song at which the first beat lands
+    pub first_beat_offset_nanos: u64,
+    /// Song playback position minus conductor clock as measured on the last frame
+    pub drift_nanos: i64,
+    /// Number of beats crossed this frame, more than one when a frame spans several beats
+    pub beats_crossed: u8,
+}
+
+#[derive(Message, Debug, Clone, Copy)]
+pub struct BeatCrossed {
+    pub beat: u8,
+    pub measure: u64,
 }
 
-pub fn initial_metronome(bpm: u64) -> Metronome {
+pub fn initial_metronome(
+    tempo_map: TempoMap,
+    time_signature: TimeSignature,
+    subdivision: Subdivision,
+) -> Metronome {
     Metronome {
         beat: 0,
-        bpm,
+        total_beats: 0,
+        bpm: tempo_map.bpm_at(0),
+        tempo_map,
+        time_signature,
+        subdivision,
         is_beat_start_frame: false,
         nanos_accumulated: Fraction::from(0),
         started: false,
+        song_position_nanos: 0,
+        first_beat_offset_nanos: 0,
+        drift_nanos: 0,
+        beats_crossed: 0,
     }
 }
 
-fn nanos_fraction_per_beat(bpm: u64) -> Fraction {
-    Fraction::new(60_000_000_000u64, bpm * 4)
+pub const fn steps_per_beat_unit(metronome: &Metronome) -> u8 {
+    metronome
+        .subdivision
+        .steps_per_beat_unit(metronome.time_signature)
+}
+
+pub const fn steps_per_measure(metronome: &Metronome) -> u8 {
+    metronome.time_signature.beats_per_measure * steps_per_beat_unit(metronome)
+}
+
+fn nanos_fraction_per_beat(metronome: &Metronome) -> Fraction {
+    nanos_fraction_per_step(metronome.bpm, steps_per_beat_unit(metronome))
+}
+
+/// Nanoseconds from the start of the song until the absolute step `beat`
+pub fn nanos_at_beat(metronome: &Metronome, beat: u64) -> u64 {
+    metronome.first_beat_offset_nanos
+        + metronome
+            .tempo_map
+            .nanos_at_beat(beat, steps_per_beat_unit(metronome))
+}
+
+/// Absolute step started by `nanos` into the song, `None` before the first beat
+pub fn beat_at_song_nanos(metronome: &Metronome, nanos: u64) -> Option<u64> {
+    nanos
+        .checked_sub(metronome.first_beat_offset_nanos)
+        .map(|nanos| {
+            metronome
+                .tempo_map
+                .beat_at_nanos(nanos, steps_per_beat_unit(metronome))
+        })
 }
 
-pub fn nanos_per_beat(bpm: u64) -> u64 {
-    nanos_fraction_per_beat(bpm).floor().try_into().unwrap()
+/// Length of the next `number_beats` steps starting from the current one, following the tempo map
+pub fn nanos_for_beats(metronome: &Metronome, number_beats: u64) -> u64 {
+    nanos_at_beat(metronome, metronome.total_beats + number_beats)
+        - nanos_at_beat(metronome, metronome.total_beats)
 }
 
 pub fn closest_beat(metronome: &Metronome) -> u8 {
-    if metronome.nanos_accumulated < nanos_fraction_per_beat(metronome.bpm) / 2 {
+    if metronome.nanos_accumulated < nanos_fraction_per_beat(metronome) / 2 {
         metronome.beat
-    } else if metronome.beat == 15 {
-        0
     } else {
-        metronome.beat + 1
+        (metronome.beat + 1) % steps_per_measure(metronome)
     }
 }
 
-pub fn within_nanos_window(metronome: &Metronome, beat: u8, nanos_window: Fraction) -> bool {
-    #[allow(clippy::cast_possible_wrap)]
-    let shifted = metronome.beat as i8 - beat as i8;
-    let on_beat_or_after_beat = (0..=8).contains(&shifted);
-    let since_start_or_till_next = if on_beat_or_after_beat {
-        metronome.nanos_accumulated
+/// Advances the conductor clock by the frame delta, then eases it towards the song's
+/// playback position (when known) and derives the current beat from it.
+/// Returns every beat crossed this frame.
+pub fn advance_conductor(
+    metronome: &mut Metronome,
+    delta: Duration,
+    playback_position: Option<Duration>,
+) -> Vec<BeatCrossed> {
+    #[allow(clippy::cast_possible_truncation)]
+    let predicted = metronome.song_position_nanos + delta.as_nanos() as u64;
+    let song_position_nanos = if let Some(playback_position) = playback_position {
+        #[allow(clippy::cast_possible_truncation)]
+        let playback_nanos = playback_position.as_nanos() as u64;
+        #[allow(clippy::cast_possible_wrap)]
+        let drift = playback_nanos as i64 - predicted as i64;
+        metronome.drift_nanos = drift;
+        if drift.abs() >= DRIFT_SNAP_NANOS {
+            playback_nanos
+        } else {
+            predicted
+                .saturating_add_signed(drift / DRIFT_CORRECTION_DIVISOR)
+                .max(metronome.song_position_nanos)
+        }
     } else {
-        nanos_fraction_per_beat(metronome.bpm) - metronome.nanos_accumulated
+        metronome.drift_nanos = 0;
+        predicted
     };
-    let beat_distance = 8 - (shifted.abs() - 8).abs();
-    let factor = (beat_distance - 1).max(0);
-    let base = since_start_or_till_next + nanos_fraction_per_beat(metronome.bpm) * factor;
-    base <= nanos_window
+
+    let previous_beats = metronome.total_beats;
+    let beats = metronome.tempo_map.beat_at_nanos(
+        song_position_nanos.saturating_sub(metronome.first_beat_offset_nanos),
+        steps_per_beat_unit(metronome),
+    );
+    let steps_per_measure = u64::from(steps_per_measure(metronome));
+    metronome.song_position_nanos = song_position_nanos;
+    metronome.total_beats = beats;
+    metronome.bpm = metronome.tempo_map.bpm_at(beats);
+    metronome.is_beat_start_frame = beats > previous_beats;
+    metronome.beats_crossed = beats
+        .saturating_sub(previous_beats)
+        .try_into()
+        .unwrap_or(u8::MAX);
+    #[allow(clippy::cast_possible_truncation)]
+    let beat = (beats % steps_per_measure) as u8;
+    metronome.beat = beat;
+    metronome.nanos_accumulated =
+        Fraction::from(song_position_nanos.saturating_sub(nanos_at_beat(metronome, beats)));
+
+    (previous_beats + 1..=beats)
+        .map(|beats| BeatCrossed {
+            #[allow(clippy::cast_possible_truncation)]
+            beat: (beats % steps_per_measure) as u8,
+            measure: beats / steps_per_measure,
+        })
+        .collect()
 }
 
 #[allow(clippy::needless_pass_by_value)]
-pub fn metronome_system(time: Res<Time>, mut metronome: ResMut<Metronome>) {
+pub fn metronome_system(
+    time: Res<Time>,
+    mut metronome: ResMut<Metronome>,
+    mut beat_crossed: MessageWriter<BeatCrossed>,
+    song_query: Query<&AudioSink, With<Song>>,
+) {
     if metronome.started {
-        let nanos_per_beat = nanos_fraction_per_beat(metronome.bpm);
-        metronome.nanos_accumulated += Fraction::from(time.delta().as_nanos());
-        if metronome.nanos_accumulated >= nanos_per_beat {
-            metronome.is_beat_start_frame = true;
-            metronome.beat = (metronome.beat + 1) % 16;
-            metronome.nanos_accumulated -= nanos_per_beat;
-        } else {
-            metronome.is_beat_start_frame = false;
-        }
+        let playback_position = song_query
+            .single()
+            .ok()
+            .filter(|audio_sink| !audio_sink.empty())
+            .map(AudioSinkPlayback::position);
+        beat_crossed.write_batch(advance_conductor(
+            &mut metronome,
+            time.delta(),
+            playback_position,
+        ));
+    } else {
+        metronome.is_beat_start_frame = false;
+        metronome.beats_crossed = 0;
     }
 }
 
-#[allow(dead_code)]
-pub fn all_beats() -> Vec<u8> {
-    vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]
+pub fn all_beats(metronome: &Metronome) -> Vec<u8> {
+    (0..steps_per_measure(metronome)).collect()
 }
 
-pub fn is_down_beat(metronome: &Metronome) -> bool {
-    down_beats().contains(&metronome.beat)
+pub fn is_down_beat(metronome: &Metronome, beat: u8) -> bool {
+    down_beats(metronome).contains(&beat)
 }
 
-pub fn down_beats() -> Vec<u8> {
-    vec![0, 4, 8, 12]
+/// The first step of every felt beat in the measure
+pub fn down_beats(metronome: &Metronome) -> Vec<u8> {
+    let beat_units_per_down_beat = if metronome.time_signature.is_compound() {
+        3
+    } else {
+        1
+    };
+    all_beats(metronome)
+        .into_iter()
+        .step_by(usize::from(
+            steps_per_beat_unit(metronome) * beat_units_per_down_beat,
+        ))
+        .collect()
 }
 
 pub fn nanos_from_beat(metronome: &Metronome, beat: u8) -> Fraction {
-    let nanos_per_beat = nanos_fraction_per_beat(metronome.bpm);
-    let distance_forward = ((i16::from(beat) - i16::from(metronome.beat) + 16) % 16) as i8;
+    let nanos_per_beat = nanos_fraction_per_beat(metronome);
+    let steps_per_measure = i16::from(steps_per_measure(metronome));
+    let distance_forward =
+        (i16::from(beat) - i16::from(metronome.beat)).rem_euclid(steps_per_measure);
+
+    let song_position = Fraction::from(metronome.song_position_nanos);
 
     if distance_forward == 0 {
         -metronome.nanos_accumulated
-    } else if distance_forward > 0 && distance_forward <= 8 {
+    } else if distance_forward <= steps_per_measure / 2 {
         // Beat is in the future
-        let time_until_next_beat = nanos_per_beat - metronome.nanos_accumulated;
         #[allow(clippy::cast_sign_loss)]
-        let additional_beats = (distance_forward - 1) as u64;
-        time_until_next_beat + nanos_per_beat * additional_beats
+        let beats_forward = distance_forward as u64;
+        Fraction::from(nanos_at_beat(
+            metronome,
+            metronome.total_beats + beats_forward,
+        )) - song_position
     } else {
         #[allow(clippy::cast_sign_loss)]
-        let beats_back = (16 - distance_forward) as u64;
-        let time_since_beat = metronome.nanos_accumulated + nanos_per_beat * beats_back;
-        -time_since_beat
+        let beats_back = (steps_per_measure - distance_forward) as u64;
+        metronome.total_beats.checked_sub(beats_back).map_or_else(
+            // Before the song started, assume the opening tempo
+            || -(metronome.nanos_accumulated + nanos_per_beat * beats_back),
+            |beat| Fraction::from(nanos_at_beat(metronome, beat)) - song_position,
+        )
     }
 }
 
+/// Which of `beats` is closest to now, with how far past it we are (negative when it's still ahead).
+/// `latency_nanos` is subtracted first, to judge inputs that arrive late.
+pub fn nearest_beat(
+    metronome: &Metronome,
+    beats: impl IntoIterator<Item = u8>,
+    latency_nanos: i64,
+) -> Option<(u8, Fraction)> {
+    let latency = Fraction::from(latency_nanos);
+    beats
+        .into_iter()
+        .map(|beat| (beat, -nanos_from_beat(metronome, beat) - latency))
+        .min_by_key(|(_, offset)| {
+            if *offset < Fraction::from(0) {
+                -*offset
+            } else {
+                *offset
+            }
+        })
+}
+
 #[derive(Debug)]
 pub struct MetronomeTimer {
     pub number_beats_duration: u8,
@@ -138,9 +514,9 @@ impl MetronomeTimer {
             MetronomeTimerState::Running {
                 ref mut beats_elapsed,
             } => {
-                if metronome.is_beat_start_frame {
-                    *beats_elapsed += 1;
-                }
+                #[allow(clippy::cast_possible_wrap)]
+                let beats_crossed = metronome.beats_crossed.min(i8::MAX as u8) as i8;
+                *beats_elapsed = beats_elapsed.saturating_add(beats_crossed);
             }
         }
     }
@@ -149,7 +525,10 @@ impl MetronomeTimer {
             MetronomeTimerState::NotStarted => false,
             MetronomeTimerState::Running { beats_elapsed, .. } => {
                 let beats_elapsed: u8 = beats_elapsed.try_into().unwrap_or(0);
-                metronome.is_beat_start_frame && beats_elapsed == self.number_beats_duration
+                metronome.is_beat_start_frame
+                    && beats_elapsed >= self.number_beats_duration
+                    && beats_elapsed.saturating_sub(metronome.beats_crossed)
+                        < self.number_beats_duration
             }
         }
     }
@@ -164,6 +543,22 @@ impl MetronomeTimer {
         }
     }
 
+    /// Share of the duration elapsed, counting progress through the current step
+    pub fn fraction(&self, metronome: &Metronome) -> f32 {
+        match self.timer_state {
+            MetronomeTimerState::NotStarted => 0.,
+            MetronomeTimerState::Running { beats_elapsed, .. } => {
+                let step_progress =
+                    metronome.nanos_accumulated / nanos_fraction_per_beat(metronome);
+                let elapsed = Fraction::from(beats_elapsed) + step_progress;
+                let fraction: f32 = (elapsed / Fraction::from(self.number_beats_duration.max(1)))
+                    .try_into()
+                    .unwrap_or(0.);
+                fraction.clamp(0., 1.)
+            }
+        }
+    }
+
     pub fn beats_elapsed(&self) -> u8 {
         match self.timer_state {
             MetronomeTimerState::NotStarted => 0,
@@ -173,3 +568,233 @@ impl MetronomeTimer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metronome(beats_per_measure: u8, beat_unit: u8, subdivision: Subdivision) -> Metronome {
+        initial_metronome(
+            TempoMap::new(120),
+            TimeSignature {
+                beats_per_measure,
+                beat_unit,
+            },
+            subdivision,
+        )
+    }
+
+    /// Steps per beat unit, steps per measure and down beats of `metronome`
+    fn assert_meter(metronome: &Metronome, per_beat_unit: u8, per_measure: u8, down: &[u8]) {
+        assert_eq!(steps_per_beat_unit(metronome), per_beat_unit);
+        assert_eq!(steps_per_measure(metronome), per_measure);
+        assert_eq!(down_beats(metronome), down);
+        for beat in all_beats(metronome) {
+            assert_eq!(
+                is_down_beat(metronome, beat),
+                down.contains(&beat),
+                "{beat}"
+            );
+        }
+    }
+
+    #[test]
+    fn four_four() {
+        assert_meter(
+            &metronome(4, 4, Subdivision::Sixteenths),
+            4,
+            16,
+            &[0, 4, 8, 12],
+        );
+    }
+
+    #[test]
+    fn three_four() {
+        assert_meter(&metronome(3, 4, Subdivision::Sixteenths), 4, 12, &[0, 4, 8]);
+    }
+
+    #[test]
+    fn six_eight_is_felt_in_two() {
+        assert_meter(&metronome(6, 8, Subdivision::Sixteenths), 2, 12, &[0, 6]);
+    }
+
+    #[test]
+    fn seven_eight() {
+        assert_meter(
+            &metronome(7, 8, Subdivision::Sixteenths),
+            2,
+            14,
+            &[0, 2, 4, 6, 8, 10, 12],
+        );
+    }
+
+    #[test]
+    fn four_four_triplets() {
+        assert_meter(
+            &metronome(4, 4, Subdivision::Triplets),
+            3,
+            12,
+            &[0, 3, 6, 9],
+        );
+    }
+
+    #[test]
+    fn six_eight_triplets() {
+        assert_meter(&metronome(6, 8, Subdivision::Triplets), 3, 18, &[0, 9]);
+    }
+
+    /// 120bpm, slowing to 60bpm at step 16 then ramping back up to 120bpm over steps 32 to 36
+    fn changing_tempo_map() -> TempoMap {
+        TempoMap::new(120)
+            .with_change(TempoChange {
+                beat: 32,
+                bpm: 120,
+                transition: TempoTransition::Ramp { beats: 4 },
+            })
+            .with_change(TempoChange {
+                beat: 16,
+                bpm: 60,
+                transition: TempoTransition::Instant,
+            })
+    }
+
+    #[test]
+    fn tempo_map_spans_follow_the_changes() {
+        let tempo_map = changing_tempo_map();
+        for beat in 0..64 {
+            assert_eq!(
+                tempo_map.bpm_at(beat),
+                tempo_map.changed_bpm_at(beat),
+                "{beat}"
+            );
+        }
+        assert_eq!(
+            [15, 16, 32, 33, 34, 35, 36].map(|beat| tempo_map.bpm_at(beat)),
+            [120, 60, 60, 75, 90, 105, 120]
+        );
+        assert_eq!(tempo_map.nanos_at_beat(16, 4), 2_000_000_000);
+        assert_eq!(tempo_map.nanos_at_beat(32, 4), 6_000_000_000);
+        assert_eq!(tempo_map.nanos_at_beat(32, 2), 12_000_000_000);
+    }
+
+    #[test]
+    fn tempo_map_nanos_and_beats_round_trip() {
+        let tempo_map = changing_tempo_map();
+        for steps_per_beat_unit in [2, 3, 4] {
+            for beat in 1..64 {
+                let nanos = tempo_map.nanos_at_beat(beat, steps_per_beat_unit);
+                assert_eq!(
+                    tempo_map.beat_at_nanos(nanos, steps_per_beat_unit),
+                    beat,
+                    "{beat}"
+                );
+                assert_eq!(
+                    tempo_map.beat_at_nanos(nanos - 1, steps_per_beat_unit),
+                    beat - 1,
+                    "{beat}"
+                );
+            }
+        }
+    }
+
+    /// At 120bpm in sixteenths every step lasts 125ms
+    const STEP: Duration = Duration::from_millis(125);
+    const FRAME: Duration = Duration::from_millis(16);
+
+    #[test]
+    fn small_drift_is_corrected_gradually() {
+        let mut metronome = metronome(4, 4, Subdivision::Sixteenths);
+        let drift = Duration::from_millis(8);
+        advance_conductor(&mut metronome, FRAME, Some(FRAME + drift));
+        assert_eq!(metronome.drift_nanos, 8_000_000);
+        let expected = FRAME + drift / 8;
+        assert_eq!(
+            u128::from(metronome.song_position_nanos),
+            expected.as_nanos()
+        );
+
+        advance_conductor(
+            &mut metronome,
+            FRAME,
+            Some((expected + FRAME).saturating_sub(drift)),
+        );
+        assert_eq!(metronome.drift_nanos, -8_000_000);
+        assert_eq!(
+            u128::from(metronome.song_position_nanos),
+            (expected + FRAME).saturating_sub(drift / 8).as_nanos()
+        );
+    }
+
+    #[test]
+    fn large_drift_snaps_to_playback() {
+        let mut metronome = metronome(4, 4, Subdivision::Sixteenths);
+        let playback = FRAME + Duration::from_millis(100);
+        advance_conductor(&mut metronome, FRAME, Some(playback));
+        assert_eq!(metronome.drift_nanos, 100_000_000);
+        assert_eq!(
+            u128::from(metronome.song_position_nanos),
+            playback.as_nanos()
+        );
+        assert_eq!(metronome.beat, 0);
+
+        let playback = STEP * 3;
+        let crossed = advance_conductor(&mut metronome, FRAME, Some(playback));
+        assert_eq!(
+            u128::from(metronome.song_position_nanos),
+            playback.as_nanos()
+        );
+        assert_eq!(crossed.len(), 3);
+    }
+
+    #[test]
+    fn stays_on_the_playback_clock_through_jittery_frames() {
+        use rand::{Rng, SeedableRng, rngs::StdRng};
+
+        const TOLERANCE: Duration = Duration::from_millis(4);
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut metronome = metronome(4, 4, Subdivision::Sixteenths);
+        let mut wall_clock = Duration::ZERO;
+        while wall_clock < Duration::from_mins(5) {
+            // Frames vary between 240fps and hitches of 40ms, and the measured delta is off by up
+            // to half a millisecond either way, while the audio device runs 0.1% fast
+            let frame = Duration::from_micros(rng.random_range(4_000..40_000));
+            let jitter = Duration::from_micros(rng.random_range(0..500));
+            let delta = if rng.random_bool(0.5) {
+                frame + jitter
+            } else {
+                frame.saturating_sub(jitter)
+            };
+            wall_clock += frame;
+            let playback = wall_clock + wall_clock / 1000;
+            advance_conductor(&mut metronome, delta, Some(playback));
+
+            let song_position = Duration::from_nanos(metronome.song_position_nanos);
+            let error = song_position.abs_diff(playback);
+            assert!(
+                error < TOLERANCE,
+                "{error:?} behind or ahead of playback at {playback:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn crosses_several_beats_in_one_frame() {
+        let mut metronome = metronome(4, 4, Subdivision::Sixteenths);
+        advance_conductor(&mut metronome, (STEP * 15).saturating_sub(FRAME * 2), None);
+        assert_eq!(metronome.total_beats, 14);
+        let crossed = advance_conductor(&mut metronome, STEP * 3, None);
+        let beats: Vec<(u8, u64)> = crossed
+            .iter()
+            .map(|crossed| (crossed.beat, crossed.measure))
+            .collect();
+        assert_eq!(beats, [(15, 0), (0, 1), (1, 1)]);
+        assert_eq!(metronome.beats_crossed, 3);
+        assert_eq!(metronome.total_beats, 17);
+        assert_eq!(metronome.beat, 1);
+        assert!(metronome.is_beat_start_frame);
+
+        assert!(advance_conductor(&mut metronome, FRAME, None).is_empty());
+        assert!(!metronome.is_beat_start_frame);
+        assert_eq!(metronome.beats_crossed, 0);
+    }
+}
@@ -5,15 +5,26 @@ use bevy::{
     mesh::{Indices, PrimitiveTopology},
     prelude::*,
 };
+use fraction::Fraction;
 
 use crate::{
-    metronome::{Metronome, is_down_beat, nanos_from_beat, nanos_per_beat},
+    NotePlayed,
+    calibration::LatencyOffsets,
+    game_state::InRun,
+    instrument::{Tuba, Violin},
+    judgement::{JudgedInput, Judgement},
+    metronome::{
+        BeatCrossed, Metronome, all_beats, is_down_beat, nanos_for_beats, nanos_from_beat,
+        steps_per_beat_unit, steps_per_measure,
+    },
     player::Player,
+    score::Score,
+    shared_meshes::SharedMeshes,
     window_size::{WINDOW_HEIGHT, WINDOW_WIDTH},
 };
 
 #[allow(clippy::cast_precision_loss)]
-const HIGHWAY_WIDTH: f32 = WINDOW_WIDTH as f32 / 30.;
+pub const HIGHWAY_WIDTH: f32 = WINDOW_WIDTH as f32 / 30.;
 #[allow(clippy::cast_precision_loss)]
 const HIGHWAY_HEIGHT: f32 = WINDOW_HEIGHT as f32 / 8.;
 
@@ -21,46 +32,146 @@ const HIGHWAY_HEIGHT: f32 = WINDOW_HEIGHT as f32 / 8.;
 const PERSPECTIVE_SCALE_MIN: f32 = 0.4; // Scale at the far end (top)
 const PERSPECTIVE_SCALE_MAX: f32 = 1.0; // Scale at the near end (bottom)
 
-/// Converts a normalized position (0.0 at bottom, 1.0 at top) to a perspective scale
-fn get_perspective_scale(normalized_y: f32) -> f32 {
-    // Interpolate between max scale (bottom) and min scale (top)
-    normalized_y.mul_add(
-        -(PERSPECTIVE_SCALE_MAX - PERSPECTIVE_SCALE_MIN),
-        PERSPECTIVE_SCALE_MAX,
-    )
-}
+/// Distance from the camera to the hit line, in highway lengths, for the far end to shrink to the min scale
+const CAMERA_DEPTH: f32 = PERSPECTIVE_SCALE_MIN / (PERSPECTIVE_SCALE_MAX - PERSPECTIVE_SCALE_MIN);
 
-/// Converts a y position within the highway to a perspective-transformed position and scale
-fn apply_perspective(y_offset: f32) -> (f32, f32) {
-    // Normalize y from -HIGHWAY_HEIGHT/2 to HIGHWAY_HEIGHT/2 into 0.0 to 1.0
-    let normalized_y = (y_offset + HIGHWAY_HEIGHT / 2.) / HIGHWAY_HEIGHT;
-    let scale = get_perspective_scale(normalized_y);
+/// How far ahead the highway shows and how quickly it scrolls
+#[derive(Resource, Debug)]
+pub struct HighwaySettings {
+    /// Beat units visible between the hit line and the far end at a scroll speed of one.
+    /// Each beat only has one line and gem on the highway, so it never shows more than a measure.
+    pub look_ahead_beats: u8,
+    /// Faster scrolling spreads notes further apart and shows less of what's coming
+    pub scroll_speed: f32,
+}
 
-    // Apply perspective: things further away (higher y, closer to 1.0) are more compressed
-    let perspective_y = y_offset;
+impl Default for HighwaySettings {
+    fn default() -> Self {
+        Self {
+            look_ahead_beats: 4,
+            scroll_speed: 1.,
+        }
+    }
+}
 
-    (perspective_y, scale)
+/// Projects a depth along the highway (0.0 at the hit line, 1.0 at the far end) to a y position
+/// within the highway and a width scale, compressing towards the vanishing point
+fn project(depth: f32) -> (f32, f32) {
+    let scale = PERSPECTIVE_SCALE_MAX * CAMERA_DEPTH / (CAMERA_DEPTH + depth);
+    let normalized_y =
+        (PERSPECTIVE_SCALE_MAX - scale) / (PERSPECTIVE_SCALE_MAX - PERSPECTIVE_SCALE_MIN);
+    (
+        normalized_y.mul_add(HIGHWAY_HEIGHT, -HIGHWAY_HEIGHT / 2.),
+        scale,
+    )
 }
 
 #[derive(Component)]
 pub struct NoteHighway;
 
+/// Lanes left to right in the order of the arrow keys that play them
+const LANES: [NotePlayed; 4] = [
+    NotePlayed::WestNote,
+    NotePlayed::SouthNote,
+    NotePlayed::NorthNote,
+    NotePlayed::EastNote,
+];
+
+/// Horizontal centre of `lane` at a given perspective scale
+fn lane_x(lane: NotePlayed, scale: f32) -> f32 {
+    let index = LANES.iter().position(|l| *l == lane).unwrap_or_default();
+    #[allow(clippy::cast_precision_loss)]
+    let offset = index as f32 - (LANES.len() - 1) as f32 / 2.;
+    offset * lane_width(scale)
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn lane_width(scale: f32) -> f32 {
+    HIGHWAY_WIDTH * scale / LANES.len() as f32
+}
+
+/// Gem colours for each instrument, shared by all of its gems
+#[derive(Resource)]
+pub struct GemMaterials {
+    violin: Handle<ColorMaterial>,
+    tuba: Handle<ColorMaterial>,
+    player: Handle<ColorMaterial>,
+}
+
+impl GemMaterials {
+    fn new(materials: &mut Assets<ColorMaterial>) -> Self {
+        Self {
+            violin: materials.add(Color::hsva(50., 0.8, 1., 1.)),
+            tuba: materials.add(Color::hsva(0., 0.8, 1., 1.)),
+            player: materials.add(Color::hsva(120., 0.7, 1., 1.)),
+        }
+    }
+
+    fn instrument(&self, is_violin: bool, is_tuba: bool) -> Handle<ColorMaterial> {
+        if is_violin {
+            self.violin.clone()
+        } else if is_tuba {
+            self.tuba.clone()
+        } else {
+            self.player.clone()
+        }
+    }
+}
+
+/// How far up the highway the next `beat` is, reaching the hit line when the beat is heard rather
+/// than when it's played. `None` once it's further away than the highway shows, which is clamped
+/// to one measure since only the next occurrence of each beat is drawn.
+fn beat_depth(
+    metronome: &Metronome,
+    latency_offsets: &LatencyOffsets,
+    settings: &HighwaySettings,
+    beat: u8,
+) -> Option<f32> {
+    let measure_nanos = nanos_for_beats(metronome, u64::from(steps_per_measure(metronome)));
+    let mut nanos_until_heard =
+        nanos_from_beat(metronome, beat) + Fraction::from(latency_offsets.audio_nanos);
+    if nanos_until_heard < Fraction::from(0) {
+        // Already heard this measure, so the next one is a measure away
+        nanos_until_heard += Fraction::from(measure_nanos);
+    }
+    let look_ahead_nanos = nanos_for_beats(
+        metronome,
+        u64::from(settings.look_ahead_beats) * u64::from(steps_per_beat_unit(metronome)),
+    );
+    #[allow(clippy::cast_precision_loss)]
+    let visible_nanos = (look_ahead_nanos as f32 / settings.scroll_speed).min(measure_nanos as f32);
+    let nanos_until_heard: f32 = nanos_until_heard.try_into().unwrap_or(f32::INFINITY);
+    let depth = nanos_until_heard / visible_nanos;
+    (depth <= 1.).then_some(depth)
+}
+
 /// Creates a trapezoid mesh for the perspective highway
 fn create_trapezoid_mesh() -> Mesh {
     let top_width = HIGHWAY_WIDTH * PERSPECTIVE_SCALE_MIN;
     let bottom_width = HIGHWAY_WIDTH * PERSPECTIVE_SCALE_MAX;
+    create_quad_mesh(
+        (-bottom_width / 2., bottom_width / 2.),
+        (-top_width / 2., top_width / 2.),
+    )
+}
+
+/// Creates a thin quad running up the highway between two lanes, narrowing with the perspective
+fn create_lane_divider_mesh(x_bottom: f32, x_top: f32) -> Mesh {
+    create_quad_mesh((x_bottom - 0.5, x_bottom + 0.5), (x_top - 0.5, x_top + 0.5))
+}
+
+/// Quad spanning the full highway height between the given left and right edges at the bottom and top
+fn create_quad_mesh(
+    (bottom_left, bottom_right): (f32, f32),
+    (top_left, top_right): (f32, f32),
+) -> Mesh {
     let height = HIGHWAY_HEIGHT;
 
-    // Define vertices for a trapezoid (wider at bottom, narrower at top)
     let vertices = vec![
-        // Bottom left
-        [-bottom_width / 2., -height / 2., 0.],
-        // Bottom right
-        [bottom_width / 2., -height / 2., 0.],
-        // Top right
-        [top_width / 2., height / 2., 0.],
-        // Top left
-        [-top_width / 2., height / 2., 0.],
+        [bottom_left, -height / 2., 0.],
+        [bottom_right, -height / 2., 0.],
+        [top_right, height / 2., 0.],
+        [top_left, height / 2., 0.],
     ];
 
     let indices = vec![
@@ -82,29 +193,47 @@ fn create_trapezoid_mesh() -> Mesh {
 #[allow(clippy::needless_pass_by_value)]
 pub fn setup_note_highway(
     metronome: Res<Metronome>,
+    shared_meshes: Res<SharedMeshes>,
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
-    let note_lines: Vec<_> = (0..8)
-        .map(|i| BeatLineBundle {
-            beat_line: BeatLine { beat: i * 2 },
+    // Draw a line every half beat unit when it splits evenly, otherwise on every step
+    let steps_per_beat_unit = steps_per_beat_unit(&metronome);
+    let line_spacing = if steps_per_beat_unit.is_multiple_of(2) {
+        steps_per_beat_unit / 2
+    } else {
+        1
+    };
+    let steps_per_measure = f32::from(steps_per_measure(&metronome));
+    let note_lines: Vec<_> = all_beats(&metronome)
+        .into_iter()
+        .step_by(usize::from(line_spacing))
+        .map(|beat| BeatLineBundle {
+            beat_line: BeatLine { beat },
+            mesh: Mesh2d(shared_meshes.rectangle.clone()),
             material: MeshMaterial2d(materials.add(Color::hsva(
                 0.,
                 0.,
-                if i % 2 == 0 { 1. } else { 0. },
+                if is_down_beat(&metronome, beat) {
+                    1.
+                } else {
+                    0.
+                },
                 0.3,
             ))),
             transform: Transform::from_xyz(
                 0.,
-                -HIGHWAY_HEIGHT / 2. + f32::from(i) * HIGHWAY_HEIGHT / 8.,
+                -HIGHWAY_HEIGHT / 2. + f32::from(beat) * HIGHWAY_HEIGHT / steps_per_measure,
                 11.,
             ),
         })
         .collect();
 
+    commands.insert_resource(GemMaterials::new(&mut materials));
     commands
         .spawn((
+            DespawnOnExit(InRun),
             NoteHighway,
             Mesh2d(meshes.add(create_trapezoid_mesh())),
             MeshMaterial2d(materials.add(Color::hsva(0., 0., 0., 0.1))),
@@ -112,25 +241,39 @@ pub fn setup_note_highway(
             Transform::from_xyz(0., WINDOW_HEIGHT as f32 / 4. - HIGHWAY_HEIGHT / 2., 10.),
         ))
         .with_children(|parent| {
-            // Apply perspective to the on-beat line
-            let y_pos = -HIGHWAY_HEIGHT / 2.;
-            let (_, scale) = apply_perspective(y_pos);
+            // The on-beat line sits on the hit line
+            let (y_pos, scale) = project(0.);
             let scaled_width = HIGHWAY_WIDTH * scale;
 
             parent.spawn((
                 OnBeatLine {
                     timer: Timer::new(
-                        Duration::from_nanos(nanos_per_beat(metronome.bpm) * 2),
+                        Duration::from_nanos(nanos_for_beats(&metronome, 2)),
                         TimerMode::Repeating,
                     ),
                 },
-                Mesh2d(meshes.add(Rectangle::new(scaled_width, 3.))),
+                Mesh2d(shared_meshes.rectangle.clone()),
                 MeshMaterial2d(materials.add(Color::hsva(246.23, 0.8908, 0.4667, 0.8))),
-                Transform::from_xyz(0., -HIGHWAY_HEIGHT / 2., 12.),
+                Transform::from_xyz(0., y_pos, 12.).with_scale(Vec3::new(scaled_width, 3., 1.)),
             ));
             for bundle in note_lines {
                 parent.spawn(bundle);
             }
+
+            // Dividers between the lanes
+            let divider_material = materials.add(Color::hsva(0., 0., 1., 0.15));
+            for divider in 1..LANES.len() {
+                #[allow(clippy::cast_precision_loss)]
+                let offset = divider as f32 - LANES.len() as f32 / 2.;
+                parent.spawn((
+                    Mesh2d(meshes.add(create_lane_divider_mesh(
+                        offset * lane_width(PERSPECTIVE_SCALE_MAX),
+                        offset * lane_width(PERSPECTIVE_SCALE_MIN),
+                    ))),
+                    MeshMaterial2d(divider_material.clone()),
+                    Transform::from_xyz(0., 0., 11.),
+                ));
+            }
         });
 }
 
@@ -147,6 +290,7 @@ pub struct BeatLine {
 #[derive(Bundle)]
 struct BeatLineBundle {
     beat_line: BeatLine,
+    mesh: Mesh2d,
     material: MeshMaterial2d<ColorMaterial>,
     transform: Transform,
 }
@@ -154,32 +298,22 @@ struct BeatLineBundle {
 #[allow(clippy::needless_pass_by_value)]
 pub fn beat_line_system(
     metronome: Res<Metronome>,
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut query: Query<(Entity, &BeatLine)>,
+    latency_offsets: Res<LatencyOffsets>,
+    settings: Res<HighwaySettings>,
+    mut query: Query<(&BeatLine, &mut Transform, &mut Visibility)>,
 ) {
-    for (entity, note_line) in &mut query {
-        if metronome.is_beat_start_frame && metronome.beat == note_line.beat {
-            commands.entity(entity).remove::<Transform>();
-        } else {
-            let percentage_from_beat =
-                nanos_from_beat(&metronome, note_line.beat) / (nanos_per_beat(metronome.bpm) * 16);
-            let distance_from_bottom: f32 = (percentage_from_beat * HIGHWAY_HEIGHT)
-                .floor()
-                .try_into()
-                .unwrap();
-            let y_pos = if distance_from_bottom >= 0. { -1. } else { 1. } * HIGHWAY_HEIGHT / 2.0
-                + distance_from_bottom;
-
-            // Apply perspective transformation
-            let (perspective_y, scale) = apply_perspective(y_pos);
-
-            // Update mesh with scaled width
-            let scaled_width = HIGHWAY_WIDTH * scale;
-            commands.entity(entity).insert((
-                Mesh2d(meshes.add(Rectangle::new(scaled_width, 1.))),
-                Transform::from_xyz(0., perspective_y, 11.).with_scale(Vec3::new(1.0, 1.0, 1.0)),
+    for (note_line, mut transform, mut visibility) in &mut query {
+        if let Some(depth) = beat_depth(&metronome, &latency_offsets, &settings, note_line.beat) {
+            let (y_pos, scale) = project(depth);
+            // Stretch the shared unit mesh to the scaled width
+            *transform = Transform::from_xyz(0., y_pos, 11.).with_scale(Vec3::new(
+                HIGHWAY_WIDTH * scale,
+                1.,
+                1.,
             ));
+            *visibility = Visibility::Inherited;
+        } else {
+            *visibility = Visibility::Hidden;
         }
     }
 }
@@ -187,29 +321,25 @@ pub fn beat_line_system(
 #[allow(clippy::needless_pass_by_value)]
 pub fn on_beat_line_system(
     time: Res<Time>,
-    mut meshes: ResMut<Assets<Mesh>>,
     metronome: Res<Metronome>,
-    mut commands: Commands,
-    mut query: Query<(Entity, &mut OnBeatLine)>,
+    mut beat_crossed: MessageReader<BeatCrossed>,
+    mut query: Query<(&mut OnBeatLine, &mut Transform)>,
 ) {
-    let (entity, mut beat_line) = query.single_mut().unwrap();
-
-    // Apply perspective to the on-beat line at the bottom
-    let y_pos = -HIGHWAY_HEIGHT / 2.;
-    let (_, scale) = apply_perspective(y_pos);
-    let scaled_width = HIGHWAY_WIDTH * scale;
+    let (mut beat_line, mut transform) = query.single_mut().unwrap();
+    let crossed_down_beat = beat_crossed
+        .read()
+        .any(|beat_crossed| is_down_beat(&metronome, beat_crossed.beat));
 
     if beat_line.timer.tick(time.delta()).just_finished() {
         beat_line.timer.reset();
         beat_line.timer.pause();
-        commands
-            .entity(entity)
-            .insert(Mesh2d(meshes.add(Rectangle::new(scaled_width, 2.))));
-    } else if metronome.is_beat_start_frame && is_down_beat(&metronome) {
+        transform.scale.y = 2.;
+    } else if crossed_down_beat {
+        beat_line
+            .timer
+            .set_duration(Duration::from_nanos(nanos_for_beats(&metronome, 2)));
         beat_line.timer.unpause();
-        commands
-            .entity(entity)
-            .insert(Mesh2d(meshes.add(Rectangle::new(scaled_width, 4.))));
+        transform.scale.y = 4.;
     }
 }
 
@@ -225,3 +355,199 @@ pub fn note_highway_system(
             player_transform.translation.y + HIGHWAY_HEIGHT / 2. + 15.;
     }
 }
+
+/// A note from an instrument's score, scrolling down its lane towards the hit line every measure
+#[derive(Component)]
+pub struct NoteGem {
+    instrument: Entity,
+    beat: u8,
+    lane: NotePlayed,
+    /// Set once judged, hiding the gem until it comes back round
+    reappear_at_nanos: Option<u64>,
+}
+
+/// Keeps a gem on the highway for every note on every score
+#[allow(clippy::needless_pass_by_value)]
+pub fn note_gem_sync_system(
+    mut commands: Commands,
+    shared_meshes: Res<SharedMeshes>,
+    gem_materials: Res<GemMaterials>,
+    highway_query: Query<Entity, With<NoteHighway>>,
+    gem_query: Query<(Entity, &NoteGem)>,
+    score_query: Query<(Entity, &Score, Has<Violin>, Has<Tuba>)>,
+    changed_score_query: Query<(), Changed<Score>>,
+) {
+    let Ok(highway_entity) = highway_query.single() else {
+        return;
+    };
+    if changed_score_query.is_empty() {
+        return;
+    }
+    for (gem_entity, gem) in &gem_query {
+        let still_scored = score_query
+            .get(gem.instrument)
+            .is_ok_and(|(_, score, ..)| score.note_at(gem.beat).is_some());
+        if !still_scored {
+            commands.entity(gem_entity).despawn();
+        }
+    }
+    for (instrument, score, is_violin, is_tuba) in &score_query {
+        let material = gem_materials.instrument(is_violin, is_tuba);
+        for beat in score.beats_in_lane(score.lane()) {
+            let has_gem = gem_query
+                .iter()
+                .any(|(_, gem)| gem.instrument == instrument && gem.beat == beat);
+            if !has_gem {
+                commands.entity(highway_entity).with_child((
+                    NoteGem {
+                        instrument,
+                        beat,
+                        lane: score.lane(),
+                        reappear_at_nanos: None,
+                    },
+                    Mesh2d(shared_meshes.rectangle.clone()),
+                    MeshMaterial2d(material.clone()),
+                    Transform::from_xyz(0., -HIGHWAY_HEIGHT, 12.),
+                    Visibility::Hidden,
+                ));
+            }
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+pub fn note_gem_system(
+    metronome: Res<Metronome>,
+    latency_offsets: Res<LatencyOffsets>,
+    settings: Res<HighwaySettings>,
+    mut gem_query: Query<(&mut NoteGem, &mut Transform, &mut Visibility)>,
+) {
+    for (mut gem, mut transform, mut visibility) in &mut gem_query {
+        if gem
+            .reappear_at_nanos
+            .is_some_and(|nanos| metronome.song_position_nanos >= nanos)
+        {
+            gem.reappear_at_nanos = None;
+        }
+        let depth = beat_depth(&metronome, &latency_offsets, &settings, gem.beat);
+        if let Some(depth) = depth
+            && gem.reappear_at_nanos.is_none()
+        {
+            let (y_pos, scale) = project(depth);
+            transform.translation = Vec3::new(lane_x(gem.lane, scale), y_pos, 12.);
+            transform.scale = Vec3::new(lane_width(scale) * 0.8, 3., 1.);
+            *visibility = Visibility::Inherited;
+        } else {
+            *visibility = Visibility::Hidden;
+        }
+    }
+}
+
+/// Burst left behind where a judged gem was
+#[derive(Component)]
+pub struct GemEffect {
+    timer: Timer,
+}
+
+const GEM_EFFECT_RADIUS: f32 = HIGHWAY_WIDTH / 8.;
+
+/// Hides the gems hit or missed by a judgement, leaving a burst in the tier's colour
+#[allow(clippy::needless_pass_by_value)]
+pub fn note_gem_judgement_system(
+    metronome: Res<Metronome>,
+    mut commands: Commands,
+    shared_meshes: Res<SharedMeshes>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut judgements: MessageReader<Judgement>,
+    highway_query: Query<Entity, With<NoteHighway>>,
+    mut gem_query: Query<(&mut NoteGem, &Transform, &mut Visibility)>,
+) {
+    let Ok(highway_entity) = highway_query.single() else {
+        return;
+    };
+    let half_measure_nanos =
+        nanos_for_beats(&metronome, u64::from(steps_per_measure(&metronome) / 2));
+    for judgement in judgements.read() {
+        let JudgedInput::Note(lane) = judgement.input else {
+            continue;
+        };
+        for (mut gem, transform, mut visibility) in &mut gem_query {
+            if gem.lane == lane && gem.beat == judgement.beat && gem.reappear_at_nanos.is_none() {
+                gem.reappear_at_nanos = Some(metronome.song_position_nanos + half_measure_nanos);
+                *visibility = Visibility::Hidden;
+                commands.entity(highway_entity).with_child((
+                    GemEffect {
+                        timer: Timer::from_seconds(0.2, TimerMode::Once),
+                    },
+                    Mesh2d(shared_meshes.circle.clone()),
+                    MeshMaterial2d(materials.add(judgement.tier.color())),
+                    Transform::from_translation(transform.translation.with_z(13.))
+                        .with_scale(Vec3::splat(GEM_EFFECT_RADIUS)),
+                ));
+            }
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+pub fn gem_effect_system(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut query: Query<(
+        Entity,
+        &mut GemEffect,
+        &mut Transform,
+        &MeshMaterial2d<ColorMaterial>,
+    )>,
+) {
+    for (entity, mut effect, mut transform, material) in &mut query {
+        if effect.timer.tick(time.delta()).just_finished() {
+            commands.entity(entity).despawn();
+        } else {
+            let fraction = effect.timer.fraction();
+            transform.scale = Vec3::splat(GEM_EFFECT_RADIUS * (1. + fraction));
+            if let Some(material) = materials.get_mut(&material.0) {
+                material.color.set_alpha(1. - fraction);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metronome::{Subdivision, TempoMap, TimeSignature, initial_metronome};
+
+    fn depths(look_ahead_beats: u8, scroll_speed: f32) -> Vec<Option<f32>> {
+        let metronome = initial_metronome(
+            TempoMap::new(120),
+            TimeSignature {
+                beats_per_measure: 4,
+                beat_unit: 4,
+            },
+            Subdivision::Sixteenths,
+        );
+        let settings = HighwaySettings {
+            look_ahead_beats,
+            scroll_speed,
+        };
+        [4, 8, 12]
+            .map(|beat| beat_depth(&metronome, &LatencyOffsets::default(), &settings, beat))
+            .to_vec()
+    }
+
+    #[test]
+    fn beats_spread_over_the_look_ahead() {
+        assert_eq!(depths(2, 1.), [Some(0.5), Some(1.), None]);
+        assert_eq!(depths(2, 2.), [Some(1.), None, None]);
+    }
+
+    #[test]
+    fn look_ahead_is_clamped_to_a_measure() {
+        let in_a_measure = [Some(0.25), Some(0.5), Some(0.75)];
+        assert_eq!(depths(4, 1.), in_a_measure);
+        assert_eq!(depths(8, 1.), in_a_measure);
+        assert_eq!(depths(2, 0.25), in_a_measure);
+    }
+}
@@ -0,0 +1,140 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    enemy::Enemy,
+    game_state::{GameState, InRun},
+    health::{DamageEvent, DamageKind, Health, PlayerHit},
+    player::Player,
+    status_effect::{StatusEffectKind, StatusEffects},
+};
+
+/// Damage dealt to the player when an enemy touches them
+#[derive(Component, Debug)]
+pub struct ContactDamage(pub u128);
+
+/// Hits are ignored until the timer runs out
+#[derive(Component, Debug)]
+pub struct Invulnerable {
+    timer: Timer,
+}
+
+const INVULNERABLE_SECONDS: f32 = 1.;
+
+/// Enemies touching the player hurt them, unless stunned
+#[allow(clippy::needless_pass_by_value)]
+pub fn enemy_contact_system(
+    rapier_context: ReadRapierContext,
+    mut damage_events: MessageWriter<DamageEvent>,
+    enemy_query: Query<(Entity, &ContactDamage, Option<&StatusEffects>), With<Enemy>>,
+    player_query: Query<Entity, With<Player>>,
+) {
+    let Ok(rapier_context) = rapier_context.single() else {
+        return;
+    };
+    let Ok(player_entity) = player_query.single() else {
+        return;
+    };
+    for (enemy_entity, contact_damage, status_effects) in enemy_query {
+        if status_effects.is_some_and(|status_effects| status_effects.has(StatusEffectKind::Stun)) {
+            continue;
+        }
+        if rapier_context
+            .contact_pair(enemy_entity, player_entity)
+            .is_some_and(|contact_pair| contact_pair.has_any_active_contact())
+        {
+            damage_events.write(DamageEvent {
+                source: enemy_entity,
+                target: player_entity,
+                amount: contact_damage.0,
+                kind: DamageKind::Contact,
+            });
+        }
+    }
+}
+
+/// Each hit leaves the player briefly unable to be hit again
+pub fn player_hit_system(mut commands: Commands, mut player_hit: MessageReader<PlayerHit>) {
+    for PlayerHit { player } in player_hit.read() {
+        commands.entity(*player).try_insert(Invulnerable {
+            timer: Timer::from_seconds(INVULNERABLE_SECONDS, TimerMode::Once),
+        });
+    }
+}
+
+/// Blinks the player while they can't be hit
+#[allow(clippy::needless_pass_by_value)]
+pub fn invulnerability_system(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Invulnerable, &Children)>,
+    mut sprite_query: Query<&mut Sprite>,
+) {
+    for (entity, mut invulnerable, children) in &mut query {
+        let finished = invulnerable.timer.tick(time.delta()).is_finished();
+        if finished {
+            commands.entity(entity).try_remove::<Invulnerable>();
+        }
+        let alpha =
+            if finished || (invulnerable.timer.elapsed().as_millis() / 100).is_multiple_of(2) {
+                1.
+            } else {
+                0.3
+            };
+        for child in children {
+            if let Ok(mut sprite) = sprite_query.get_mut(*child) {
+                sprite.color.set_alpha(alpha);
+            }
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+pub fn player_death_system(
+    player_query: Query<&Health, (With<Player>, Changed<Health>)>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if player_query.iter().any(|health| health.current_health == 0) {
+        next_state.set(GameState::GameOver);
+    }
+}
+
+#[derive(Component)]
+pub struct PlayerHealthBarFill;
+
+pub fn setup_player_health_bar(mut commands: Commands) {
+    commands.spawn((
+        DespawnOnExit(InRun),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(32.),
+            left: Val::Percent(20.),
+            width: Val::Percent(20.),
+            height: Val::Px(12.),
+            ..default()
+        },
+        BackgroundColor(Color::BLACK.with_alpha(0.6)),
+        children![(
+            PlayerHealthBarFill,
+            Node {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                ..default()
+            },
+            BackgroundColor(Color::hsva(0., 0.8, 0.9, 1.)),
+        )],
+    ));
+}
+
+pub fn player_health_bar_system(
+    player_query: Query<&Health, (With<Player>, Changed<Health>)>,
+    mut fill_query: Query<&mut Node, With<PlayerHealthBarFill>>,
+) {
+    for health in &player_query {
+        #[allow(clippy::cast_precision_loss)]
+        let fraction = health.current_health as f32 / health.max_health as f32;
+        for mut node in &mut fill_query {
+            node.width = Val::Percent(fraction * 100.);
+        }
+    }
+}
@@ -0,0 +1,334 @@
+use bevy::prelude::*;
+
+use crate::{
+    NotePlayed,
+    metronome::{Metronome, down_beats, steps_per_beat_unit, steps_per_measure},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteLength {
+    Sixteenth,
+    Eighth,
+    DottedEighth,
+    Quarter,
+    DottedQuarter,
+    Half,
+    DottedHalf,
+    Whole,
+}
+
+impl NoteLength {
+    const fn sixteenths(self) -> u16 {
+        match self {
+            Self::Sixteenth => 1,
+            Self::Eighth => 2,
+            Self::DottedEighth => 3,
+            Self::Quarter => 4,
+            Self::DottedQuarter => 6,
+            Self::Half => 8,
+            Self::DottedHalf => 12,
+            Self::Whole => 16,
+        }
+    }
+
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Sixteenth => "Sixteenth",
+            Self::Eighth => "Eighth",
+            Self::DottedEighth => "Dotted eighth",
+            Self::Quarter => "Quarter",
+            Self::DottedQuarter => "Dotted quarter",
+            Self::Half => "Half",
+            Self::DottedHalf => "Dotted half",
+            Self::Whole => "Whole",
+        }
+    }
+
+    /// How many metronome steps the note lasts, at least one
+    pub fn steps(self, metronome: &Metronome) -> u8 {
+        let steps_per_whole_note = u16::from(metronome.time_signature.beat_unit)
+            * u16::from(steps_per_beat_unit(metronome));
+        (self.sixteenths() * steps_per_whole_note / 16)
+            .clamp(1, u16::from(u8::MAX))
+            .try_into()
+            .unwrap_or(u8::MAX)
+    }
+}
+
+/// How loud a note is played, from pianississimo to fortississimo
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Dynamic {
+    Ppp,
+    Pp,
+    P,
+    Mp,
+    Mf,
+    F,
+    Ff,
+    Fff,
+}
+
+impl Dynamic {
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Ppp => "ppp",
+            Self::Pp => "pp",
+            Self::P => "p",
+            Self::Mp => "mp",
+            Self::Mf => "mf",
+            Self::F => "f",
+            Self::Ff => "ff",
+            Self::Fff => "fff",
+        }
+    }
+}
+
+/// Extra behaviour a note gives the ability it triggers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Element {
+    /// Lasers poison what they hit, damage that goes through armor
+    Poison,
+    /// Bullets explode on impact, burning everything nearby
+    Fire,
+    /// Rings push harder, hurting enemies the further they're pushed
+    Water,
+}
+
+impl Element {
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Poison => "poison",
+            Self::Fire => "fire",
+            Self::Water => "water",
+        }
+    }
+
+    pub const fn color(self) -> Color {
+        match self {
+            Self::Poison => Color::hsva(100., 0.8, 0.9, 1.),
+            Self::Fire => Color::hsva(25., 0.9, 1., 1.),
+            Self::Water => Color::hsva(200., 0.8, 1., 1.),
+        }
+    }
+}
+
+/// Name of a note, which decides its element
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteName {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+}
+
+impl NoteName {
+    pub const ALL: [Self; 7] = [
+        Self::A,
+        Self::B,
+        Self::C,
+        Self::D,
+        Self::E,
+        Self::F,
+        Self::G,
+    ];
+
+    const fn label(self) -> &'static str {
+        match self {
+            Self::A => "A",
+            Self::B => "B",
+            Self::C => "C",
+            Self::D => "D",
+            Self::E => "E",
+            Self::F => "F",
+            Self::G => "G",
+        }
+    }
+
+    /// G is the only plain note
+    pub const fn element(self) -> Option<Element> {
+        match self {
+            Self::A | Self::B => Some(Element::Poison),
+            Self::C | Self::D => Some(Element::Fire),
+            Self::E | Self::F => Some(Element::Water),
+            Self::G => None,
+        }
+    }
+}
+
+/// A note placed on a score, played by pressing the score's lane on time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Note {
+    /// How long the ability the note triggers lasts
+    pub length: NoteLength,
+    /// How strong the ability the note triggers is
+    pub dynamic: Dynamic,
+    pub name: NoteName,
+}
+
+impl Note {
+    pub const fn element(self) -> Option<Element> {
+        self.name.element()
+    }
+
+    pub fn label(self) -> String {
+        let label = format!(
+            "{}\n{}\n{}",
+            self.name.label(),
+            self.length.label(),
+            self.dynamic.label()
+        );
+        match self.element() {
+            Some(element) => format!("{label}\n{}", element.label()),
+            None => label,
+        }
+    }
+}
+
+/// One measure of steps, each optionally holding a note for the instrument to play in its lane
+#[derive(Component, Debug, Clone)]
+pub struct Score {
+    lane: NotePlayed,
+    steps: Vec<Option<Note>>,
+}
+
+impl Score {
+    pub fn new(metronome: &Metronome, lane: NotePlayed) -> Self {
+        Self {
+            lane,
+            steps: vec![None; usize::from(steps_per_measure(metronome))],
+        }
+    }
+
+    pub const fn lane(&self) -> NotePlayed {
+        self.lane
+    }
+
+    pub fn steps(&self) -> &[Option<Note>] {
+        &self.steps
+    }
+
+    pub fn with_note(mut self, beat: u8, note: Note) -> Self {
+        if let Some(step) = self.steps.get_mut(usize::from(beat)) {
+            *step = Some(note);
+        }
+        self
+    }
+
+    pub fn note_at(&self, beat: u8) -> Option<Note> {
+        self.steps.get(usize::from(beat)).copied().flatten()
+    }
+
+    /// Puts `note` on `beat`, handing back whatever was there before
+    pub fn replace(&mut self, beat: u8, note: Option<Note>) -> Option<Note> {
+        self.steps
+            .get_mut(usize::from(beat))
+            .and_then(|step| std::mem::replace(step, note))
+    }
+
+    /// Steps holding a note, if the score is played in `lane`
+    pub fn beats_in_lane(&self, lane: NotePlayed) -> impl Iterator<Item = u8> + '_ {
+        self.steps
+            .iter()
+            .zip(0..)
+            .filter(move |(note, _)| self.lane == lane && note.is_some())
+            .map(|(_, beat)| beat)
+    }
+}
+
+/// `note` in `lane` on every `every`th down beat of the measure, starting from the `first`
+pub fn starting_score(
+    metronome: &Metronome,
+    lane: NotePlayed,
+    note: Note,
+    first: usize,
+    every: usize,
+) -> Score {
+    down_beats(metronome)
+        .into_iter()
+        .skip(first)
+        .step_by(every)
+        .fold(Score::new(metronome, lane), |score, beat| {
+            score.with_note(beat, note)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metronome::{Subdivision, TempoMap, TimeSignature, initial_metronome};
+
+    const LENGTHS: [NoteLength; 4] = [
+        NoteLength::Sixteenth,
+        NoteLength::DottedEighth,
+        NoteLength::Quarter,
+        NoteLength::Whole,
+    ];
+
+    fn steps(
+        beats_per_measure: u8,
+        beat_unit: u8,
+        subdivision: Subdivision,
+        lengths: [NoteLength; 4],
+    ) -> [u8; 4] {
+        let metronome = initial_metronome(
+            TempoMap::new(120),
+            TimeSignature {
+                beats_per_measure,
+                beat_unit,
+            },
+            subdivision,
+        );
+        lengths.map(|length| length.steps(&metronome))
+    }
+
+    #[test]
+    fn note_lengths_follow_the_beat_unit() {
+        assert_eq!(steps(4, 4, Subdivision::Sixteenths, LENGTHS), [1, 3, 4, 16]);
+        assert_eq!(steps(6, 8, Subdivision::Sixteenths, LENGTHS), [1, 3, 4, 16]);
+        assert_eq!(steps(2, 2, Subdivision::Sixteenths, LENGTHS), [1, 3, 4, 16]);
+        assert_eq!(
+            steps(7, 16, Subdivision::Sixteenths, LENGTHS),
+            [1, 3, 4, 16]
+        );
+    }
+
+    #[test]
+    fn note_lengths_follow_triplets() {
+        assert_eq!(steps(6, 8, Subdivision::Triplets, LENGTHS), [1, 4, 6, 24]);
+        assert_eq!(
+            steps(
+                4,
+                4,
+                Subdivision::Triplets,
+                [
+                    NoteLength::Eighth,
+                    NoteLength::DottedQuarter,
+                    NoteLength::Half,
+                    NoteLength::Whole
+                ]
+            ),
+            [1, 4, 6, 12]
+        );
+    }
+
+    #[test]
+    fn notes_shorter_than_a_step_last_one_step() {
+        assert_eq!(steps(4, 4, Subdivision::Triplets, LENGTHS)[0], 1);
+        assert_eq!(steps(3, 2, Subdivision::Triplets, LENGTHS)[..2], [1, 1]);
+    }
+
+    #[test]
+    fn every_element_has_a_note_name() {
+        for element in [Element::Poison, Element::Fire, Element::Water] {
+            assert!(
+                NoteName::ALL
+                    .iter()
+                    .any(|name| name.element() == Some(element))
+            );
+        }
+        assert_eq!(NoteName::G.element(), None);
+    }
+}
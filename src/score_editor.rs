@@ -0,0 +1,250 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_egui::{EguiContexts, egui};
+
+use crate::{
+    instrument::{Tuba, Violin},
+    level_up::NoteInventory,
+    score::{Note, Score},
+};
+
+/// Somewhere a note can sit while the scores are being arranged
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteSlot {
+    Inventory(usize),
+    Step(Entity, u8),
+}
+
+/// Keyboard cursor over the instrument rows, with the inventory as the last row
+#[derive(Resource, Debug, Default)]
+pub struct ScoreEditor {
+    cursor_row: usize,
+    cursor_column: usize,
+    /// Slot picked up by a click or Enter, moved on the next one
+    selected: Option<NoteSlot>,
+}
+
+impl ScoreEditor {
+    /// Picks `slot` up, or moves what was picked up into it
+    const fn select(&mut self, slot: NoteSlot) -> Option<(NoteSlot, NoteSlot)> {
+        if let Some(selected) = self.selected.take() {
+            Some((selected, slot))
+        } else {
+            self.selected = Some(slot);
+            None
+        }
+    }
+}
+
+#[derive(SystemParam)]
+pub struct Arrangement<'w, 's> {
+    inventory: ResMut<'w, NoteInventory>,
+    scores: Query<'w, 's, (Entity, &'static mut Score, Has<Violin>, Has<Tuba>)>,
+}
+
+impl Arrangement<'_, '_> {
+    /// Scored entities with their display names, the maestro first
+    fn rows(&self) -> Vec<(Entity, &'static str)> {
+        let mut rows: Vec<_> = self
+            .scores
+            .iter()
+            .map(|(entity, _, is_violin, is_tuba)| {
+                let (order, name) = if is_violin {
+                    (1, "Violin")
+                } else if is_tuba {
+                    (2, "Tuba")
+                } else {
+                    (0, "Maestro")
+                };
+                (order, entity, name)
+            })
+            .collect();
+        rows.sort_unstable();
+        rows.into_iter()
+            .map(|(_, entity, name)| (entity, name))
+            .collect()
+    }
+
+    fn take(&mut self, slot: NoteSlot) -> Option<Note> {
+        match slot {
+            NoteSlot::Inventory(index) => {
+                (index < self.inventory.0.len()).then(|| self.inventory.0.remove(index))
+            }
+            NoteSlot::Step(entity, beat) => self
+                .scores
+                .get_mut(entity)
+                .ok()
+                .and_then(|(_, mut score, ..)| score.replace(beat, None)),
+        }
+    }
+
+    /// Moves the note in `from` to `to`, swapping with anything already there
+    fn move_note(&mut self, from: NoteSlot, to: NoteSlot) {
+        if from == to {
+            return;
+        }
+        let Some(note) = self.take(from) else {
+            return;
+        };
+        let displaced = match to {
+            NoteSlot::Inventory(_) => {
+                self.inventory.0.push(note);
+                None
+            }
+            NoteSlot::Step(entity, beat) => match self.scores.get_mut(entity) {
+                Ok((_, mut score, ..)) => score.replace(beat, Some(note)),
+                Err(_) => Some(note),
+            },
+        };
+        if let Some(displaced) = displaced {
+            match from {
+                NoteSlot::Inventory(_) => self.inventory.0.push(displaced),
+                NoteSlot::Step(entity, beat) => {
+                    if let Ok((_, mut score, ..)) = self.scores.get_mut(entity) {
+                        score.replace(beat, Some(displaced));
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub fn setup_score_editor(mut commands: Commands) {
+    commands.insert_resource(ScoreEditor::default());
+}
+
+fn slot_button(
+    ui: &mut egui::Ui,
+    note: Option<Note>,
+    has_cursor: bool,
+    is_selected: bool,
+) -> egui::Response {
+    let mut button = egui::Button::new(note.map_or_else(|| "·".to_string(), Note::label))
+        .min_size(egui::vec2(56., 40.))
+        .sense(egui::Sense::click_and_drag())
+        .selected(has_cursor);
+    if is_selected {
+        button = button.fill(egui::Color32::DARK_GREEN);
+    }
+    ui.add(button)
+}
+
+/// Clicks pick a note up and put it down, dragging moves it straight away
+fn slot_interaction(
+    response: &egui::Response,
+    slot: NoteSlot,
+    editor: &mut ScoreEditor,
+) -> Option<(NoteSlot, NoteSlot)> {
+    response.dnd_set_drag_payload(slot);
+    if let Some(from) = response.dnd_release_payload::<NoteSlot>() {
+        editor.selected = None;
+        Some((*from, slot))
+    } else if response.clicked() {
+        editor.select(slot)
+    } else {
+        None
+    }
+}
+
+/// Draws each instrument's measure as a row of steps above the inventory
+pub fn score_editor_ui_system(
+    mut contexts: EguiContexts,
+    mut editor: ResMut<ScoreEditor>,
+    mut arrangement: Arrangement,
+) -> Result {
+    let ctx = contexts.ctx_mut()?;
+    let rows = arrangement.rows();
+    let mut moved = None;
+
+    egui::Window::new("Score")
+        .anchor(egui::Align2::CENTER_BOTTOM, egui::vec2(0., -16.))
+        .resizable(false)
+        .show(ctx, |ui| {
+            for (row, (entity, name)) in rows.iter().enumerate() {
+                let Ok((_, score, ..)) = arrangement.scores.get(*entity) else {
+                    continue;
+                };
+                ui.label(*name);
+                ui.horizontal(|ui| {
+                    for (column, note) in score.steps().iter().enumerate() {
+                        #[allow(clippy::cast_possible_truncation)]
+                        let slot = NoteSlot::Step(*entity, column as u8);
+                        let has_cursor = editor.cursor_row == row && editor.cursor_column == column;
+                        let is_selected = editor.selected == Some(slot);
+                        let response = slot_button(ui, *note, has_cursor, is_selected);
+                        moved = moved.or_else(|| slot_interaction(&response, slot, &mut editor));
+                    }
+                });
+            }
+
+            ui.separator();
+            ui.label("Inventory");
+            ui.horizontal_wrapped(|ui| {
+                let inventory_row = rows.len();
+                let notes = arrangement.inventory.0.iter().copied().map(Some);
+                // A trailing empty slot to drop notes back into the inventory
+                for (column, note) in notes.chain([None]).enumerate() {
+                    let slot = NoteSlot::Inventory(column);
+                    let has_cursor =
+                        editor.cursor_row == inventory_row && editor.cursor_column == column;
+                    let is_selected = editor.selected == Some(slot);
+                    let response = slot_button(ui, note, has_cursor, is_selected);
+                    moved = moved.or_else(|| slot_interaction(&response, slot, &mut editor));
+                }
+            });
+            ui.label("Arrows move, Enter picks up and places, Tab returns to the song");
+        });
+
+    if let Some((from, to)) = moved {
+        arrangement.move_note(from, to);
+    }
+    Ok(())
+}
+
+#[allow(clippy::needless_pass_by_value)]
+pub fn score_editor_keyboard_system(
+    input: Res<ButtonInput<KeyCode>>,
+    mut editor: ResMut<ScoreEditor>,
+    mut arrangement: Arrangement,
+) {
+    let rows = arrangement.rows();
+    let inventory_row = rows.len();
+    let columns_in_row = |row: usize| {
+        rows.get(row).map_or_else(
+            || arrangement.inventory.0.len() + 1,
+            |(entity, _)| {
+                arrangement
+                    .scores
+                    .get(*entity)
+                    .map_or(0, |(_, score, ..)| score.steps().len())
+            },
+        )
+    };
+
+    if input.just_pressed(KeyCode::ArrowUp) {
+        editor.cursor_row = editor.cursor_row.saturating_sub(1);
+    }
+    if input.just_pressed(KeyCode::ArrowDown) {
+        editor.cursor_row = (editor.cursor_row + 1).min(inventory_row);
+    }
+    if input.just_pressed(KeyCode::ArrowLeft) {
+        editor.cursor_column = editor.cursor_column.saturating_sub(1);
+    }
+    if input.just_pressed(KeyCode::ArrowRight) {
+        editor.cursor_column += 1;
+    }
+    editor.cursor_column = editor
+        .cursor_column
+        .min(columns_in_row(editor.cursor_row).saturating_sub(1));
+
+    if input.just_pressed(KeyCode::Enter) {
+        #[allow(clippy::cast_possible_truncation)]
+        let slot = rows
+            .get(editor.cursor_row)
+            .map_or(NoteSlot::Inventory(editor.cursor_column), |(entity, _)| {
+                NoteSlot::Step(*entity, editor.cursor_column as u8)
+            });
+        if let Some((from, to)) = editor.select(slot) {
+            arrangement.move_note(from, to);
+        }
+    }
+}
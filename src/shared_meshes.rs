@@ -0,0 +1,118 @@
+use bevy::prelude::*;
+
+/// Unit shapes reused by anything that changes size often, resized through its `Transform`
+#[derive(Resource)]
+pub struct SharedMeshes {
+    /// One by one rectangle
+    pub rectangle: Handle<Mesh>,
+    /// Circle with a radius of one
+    pub circle: Handle<Mesh>,
+    /// Rhombus one tall and two thirds as wide, the shape of an xp gem
+    pub gem: Handle<Mesh>,
+}
+
+pub fn setup_shared_meshes(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    commands.insert_resource(SharedMeshes {
+        rectangle: meshes.add(Rectangle::new(1., 1.)),
+        circle: meshes.add(Circle::new(1.)),
+        gem: meshes.add(Rhombus::new(2. / 3., 1.)),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::asset::AssetPlugin;
+
+    use super::*;
+    use crate::{
+        aoe::{aoe_bundle, aoe_stats, aoe_system, on_aoe_add},
+        calibration::LatencyOffsets,
+        health::{Health, health_bar_bundle, health_bar_system, on_health_bar_add},
+        metronome::{
+            BeatCrossed, Metronome, Subdivision, TempoMap, TimeSignature, advance_conductor,
+            initial_metronome,
+        },
+        note_highway::{
+            HighwaySettings, beat_line_system, on_beat_line_system, setup_note_highway,
+        },
+        score::Dynamic,
+    };
+
+    fn advance_song(
+        mut metronome: ResMut<Metronome>,
+        mut beat_crossed: MessageWriter<BeatCrossed>,
+    ) {
+        beat_crossed.write_batch(advance_conductor(
+            &mut metronome,
+            Duration::from_millis(16),
+            None,
+        ));
+    }
+
+    fn hurt_everything(mut query: Query<&mut Health>) {
+        for mut health in &mut query {
+            health.current_health =
+                (health.current_health + health.max_health - 1) % health.max_health;
+        }
+    }
+
+    #[test]
+    fn mesh_count_stays_constant_across_frames() {
+        let mut metronome = initial_metronome(
+            TempoMap::new(120),
+            TimeSignature {
+                beats_per_measure: 4,
+                beat_unit: 4,
+            },
+            Subdivision::Sixteenths,
+        );
+        metronome.started = true;
+
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .init_asset::<Mesh>()
+            .init_asset::<ColorMaterial>()
+            .insert_resource(metronome)
+            .init_resource::<LatencyOffsets>()
+            .init_resource::<HighwaySettings>()
+            .add_message::<BeatCrossed>()
+            .add_observer(on_health_bar_add)
+            .add_observer(on_aoe_add)
+            .add_systems(Startup, (setup_shared_meshes, setup_note_highway).chain())
+            .add_systems(
+                Update,
+                (
+                    advance_song,
+                    hurt_everything,
+                    beat_line_system,
+                    on_beat_line_system,
+                    health_bar_system,
+                    aoe_system,
+                )
+                    .chain(),
+            );
+        app.update();
+
+        for _ in 0..4 {
+            app.world_mut().spawn((
+                Health {
+                    max_health: 5,
+                    current_health: 5,
+                },
+                Transform::default(),
+                children![health_bar_bundle()],
+            ));
+        }
+        app.world_mut()
+            .spawn(aoe_bundle(aoe_stats(Dynamic::Mf), u8::MAX, 4, None));
+        app.update();
+
+        let mesh_count = app.world().resource::<Assets<Mesh>>().len();
+        for _ in 0..500 {
+            app.update();
+            assert_eq!(app.world().resource::<Assets<Mesh>>().len(), mesh_count);
+        }
+    }
+}
@@ -1,39 +1,29 @@
-use std::time::Duration;
-
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::{KinematicCharacterController, Velocity};
 
 use crate::{
     aoe::AoeDuration,
-    metronome::{Metronome, nanos_per_beat},
+    metronome::{Metronome, MetronomeTimer},
 };
 
 #[derive(Component)]
 pub struct Slide {
     velocity: f32,
     direction: Vec2,
-    timer: Timer,
+    timer: MetronomeTimer,
 }
 
-pub fn initial_slide(
-    velocity: f32,
-    direction: Vec2,
-    num_beats_duration: u8,
-    metronome: &Metronome,
-) -> Slide {
+pub const fn initial_slide(velocity: f32, direction: Vec2, num_beats_duration: u8) -> Slide {
     Slide {
         velocity,
         direction,
-        timer: Timer::new(
-            Duration::from_nanos(nanos_per_beat(metronome.bpm) * u64::from(num_beats_duration)),
-            TimerMode::Repeating,
-        ),
+        timer: MetronomeTimer::new(num_beats_duration),
     }
 }
 
 #[allow(clippy::needless_pass_by_value)]
 pub fn slide_system(
-    time: Res<Time>,
+    metronome: Res<Metronome>,
     mut commands: Commands,
     mut query: Query<
         (
@@ -46,12 +36,17 @@ pub fn slide_system(
     >,
 ) {
     for (entity, kinematic_character_controller, velocity, mut slide) in &mut query {
-        slide.timer.tick(time.delta());
-        if slide.timer.just_finished() {
+        slide.timer.tick(&metronome);
+        if slide.timer.just_finished(&metronome) {
             commands.entity(entity).try_remove::<Slide>();
             if let Some(mut velocity) = velocity {
                 velocity.linvel = Vec2::ZERO;
             }
+        } else if !metronome.started {
+            // Hold the slide where it is until the song resumes
+            if let Some(mut velocity) = velocity {
+                velocity.linvel = Vec2::ZERO;
+            }
         } else if let Some(mut kinematic_character_controller) = kinematic_character_controller {
             kinematic_character_controller.translation =
                 Some(slide.direction.normalize_or_zero() * slide.velocity);
@@ -60,3 +55,70 @@ pub fn slide_system(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::metronome::{
+        Subdivision, TempoMap, TimeSignature, advance_conductor, initial_metronome,
+    };
+
+    fn advance_song(mut metronome: ResMut<Metronome>) {
+        if metronome.started {
+            advance_conductor(&mut metronome, Duration::from_millis(16), None);
+        }
+    }
+
+    fn set_started(app: &mut App, started: bool) {
+        app.world_mut().resource_mut::<Metronome>().started = started;
+    }
+
+    fn run_frames(app: &mut App, frames: usize) {
+        for _ in 0..frames {
+            app.update();
+        }
+    }
+
+    #[test]
+    fn pausing_mid_slide_holds_it_until_the_song_resumes() {
+        let mut metronome = initial_metronome(
+            TempoMap::new(120),
+            TimeSignature {
+                beats_per_measure: 4,
+                beat_unit: 4,
+            },
+            Subdivision::Sixteenths,
+        );
+        metronome.started = true;
+        let mut app = App::new();
+        app.insert_resource(metronome)
+            .add_systems(Update, (advance_song, slide_system).chain());
+        let entity = app
+            .world_mut()
+            .spawn((Velocity::zero(), initial_slide(5., Vec2::X, 4)))
+            .id();
+        let linvel = |app: &App| app.world().get::<Velocity>(entity).unwrap().linvel;
+        let sliding = |app: &App| app.world().get::<Slide>(entity).is_some();
+
+        // A step lasts 125ms, so 10 frames is one step into the slide
+        run_frames(&mut app, 10);
+        assert!(sliding(&app));
+        assert_eq!(linvel(&app), Vec2::X * 50.);
+
+        set_started(&mut app, false);
+        run_frames(&mut app, 100);
+        assert!(sliding(&app));
+        assert_eq!(linvel(&app), Vec2::ZERO);
+
+        set_started(&mut app, true);
+        run_frames(&mut app, 10);
+        assert!(sliding(&app));
+        assert_eq!(linvel(&app), Vec2::X * 50.);
+
+        run_frames(&mut app, 20);
+        assert!(!sliding(&app));
+        assert_eq!(linvel(&app), Vec2::ZERO);
+    }
+}
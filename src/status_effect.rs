@@ -0,0 +1,288 @@
+use bevy::prelude::*;
+
+use crate::{
+    health::{DamageEvent, DamageKind},
+    metronome::{BeatCrossed, Metronome, MetronomeTimer, is_down_beat},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusEffectKind {
+    Poison,
+    Burn,
+    /// Halves movement
+    Slow,
+    /// Stops movement and attacks
+    Stun,
+}
+
+/// What happens when an effect is applied to a target already suffering from it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stacking {
+    /// Restarts the duration
+    Refresh,
+    /// Adds a stack, up to `max_stacks`, and restarts the duration
+    Stack { max_stacks: u8 },
+}
+
+impl StatusEffectKind {
+    const fn stacking(self) -> Stacking {
+        match self {
+            Self::Poison => Stacking::Stack { max_stacks: 5 },
+            Self::Burn | Self::Slow | Self::Stun => Stacking::Refresh,
+        }
+    }
+
+    /// Damage dealt on each down beat by every stack
+    const fn damage_per_stack(self) -> Option<(u128, DamageKind)> {
+        match self {
+            Self::Poison => Some((1, DamageKind::Poison)),
+            Self::Burn => Some((2, DamageKind::Burn)),
+            Self::Slow | Self::Stun => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct StatusEffect {
+    kind: StatusEffectKind,
+    source: Entity,
+    stacks: u8,
+    timer: MetronomeTimer,
+}
+
+#[derive(Component, Debug, Default)]
+pub struct StatusEffects(Vec<StatusEffect>);
+
+impl StatusEffects {
+    pub fn has(&self, kind: StatusEffectKind) -> bool {
+        self.0.iter().any(|effect| effect.kind == kind)
+    }
+
+    /// Scales movement, zero while stunned
+    pub fn speed_multiplier(&self) -> f32 {
+        if self.has(StatusEffectKind::Stun) {
+            0.
+        } else if self.has(StatusEffectKind::Slow) {
+            0.5
+        } else {
+            1.
+        }
+    }
+}
+
+/// Status effects that are ignored when applied
+#[derive(Component, Debug)]
+pub struct Immunities(pub Vec<StatusEffectKind>);
+
+#[derive(Message, Debug, Clone, Copy)]
+pub struct ApplyStatusEffect {
+    pub source: Entity,
+    pub target: Entity,
+    pub kind: StatusEffectKind,
+    /// Duration in steps
+    pub beats: u8,
+}
+
+pub fn apply_status_effect_system(
+    mut apply_status_effects: MessageReader<ApplyStatusEffect>,
+    mut query: Query<(&mut StatusEffects, Option<&Immunities>)>,
+) {
+    for apply in apply_status_effects.read() {
+        let Ok((mut status_effects, immunities)) = query.get_mut(apply.target) else {
+            continue;
+        };
+        if immunities.is_some_and(|immunities| immunities.0.contains(&apply.kind)) {
+            continue;
+        }
+        if let Some(effect) = status_effects
+            .0
+            .iter_mut()
+            .find(|effect| effect.kind == apply.kind)
+        {
+            if let Stacking::Stack { max_stacks } = apply.kind.stacking() {
+                effect.stacks = (effect.stacks + 1).min(max_stacks);
+            }
+            effect.source = apply.source;
+            effect.timer = MetronomeTimer::new(apply.beats);
+        } else {
+            status_effects.0.push(StatusEffect {
+                kind: apply.kind,
+                source: apply.source,
+                stacks: 1,
+                timer: MetronomeTimer::new(apply.beats),
+            });
+        }
+    }
+}
+
+/// Counts down every effect and deals damage over time on down beats
+#[allow(clippy::needless_pass_by_value)]
+pub fn status_effect_system(
+    metronome: Res<Metronome>,
+    mut beat_crossed: MessageReader<BeatCrossed>,
+    mut damage_events: MessageWriter<DamageEvent>,
+    mut query: Query<(Entity, &mut StatusEffects)>,
+) {
+    let on_down_beat = beat_crossed
+        .read()
+        .any(|beat_crossed| is_down_beat(&metronome, beat_crossed.beat));
+    for (entity, mut status_effects) in &mut query {
+        for effect in &mut status_effects.0 {
+            effect.timer.tick(&metronome);
+            if on_down_beat
+                && !effect.timer.finished()
+                && let Some((damage, kind)) = effect.kind.damage_per_stack()
+            {
+                damage_events.write(DamageEvent {
+                    source: effect.source,
+                    target: entity,
+                    amount: damage * u128::from(effect.stacks),
+                    kind,
+                });
+            }
+        }
+        status_effects.0.retain(|effect| !effect.timer.finished());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::metronome::{
+        Subdivision, TempoMap, TimeSignature, advance_conductor, initial_metronome,
+    };
+
+    /// Moves the song on by exactly one step every update
+    fn advance_one_step(
+        mut metronome: ResMut<Metronome>,
+        mut beat_crossed: MessageWriter<BeatCrossed>,
+    ) {
+        beat_crossed.write_batch(advance_conductor(
+            &mut metronome,
+            Duration::from_millis(125),
+            None,
+        ));
+    }
+
+    fn app() -> App {
+        let mut metronome = initial_metronome(
+            TempoMap::new(120),
+            TimeSignature {
+                beats_per_measure: 4,
+                beat_unit: 4,
+            },
+            Subdivision::Sixteenths,
+        );
+        metronome.started = true;
+
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(metronome)
+            .add_message::<BeatCrossed>()
+            .add_message::<ApplyStatusEffect>()
+            .add_message::<DamageEvent>()
+            .add_systems(
+                Update,
+                (
+                    advance_one_step,
+                    apply_status_effect_system,
+                    status_effect_system,
+                )
+                    .chain(),
+            );
+        app
+    }
+
+    fn apply(app: &mut App, target: Entity, kind: StatusEffectKind, beats: u8) {
+        app.world_mut().write_message(ApplyStatusEffect {
+            source: Entity::PLACEHOLDER,
+            target,
+            kind,
+            beats,
+        });
+        app.update();
+    }
+
+    fn effect(app: &App, target: Entity, kind: StatusEffectKind) -> Option<&StatusEffect> {
+        app.world()
+            .get::<StatusEffects>(target)
+            .unwrap()
+            .0
+            .iter()
+            .find(|effect| effect.kind == kind)
+    }
+
+    #[test]
+    fn poison_stacks_up_to_its_cap() {
+        let mut app = app();
+        let target = app.world_mut().spawn(StatusEffects::default()).id();
+        for stacks in 1..=5 {
+            apply(&mut app, target, StatusEffectKind::Poison, 8);
+            assert_eq!(
+                effect(&app, target, StatusEffectKind::Poison)
+                    .unwrap()
+                    .stacks,
+                stacks
+            );
+        }
+        apply(&mut app, target, StatusEffectKind::Poison, 8);
+        assert_eq!(
+            effect(&app, target, StatusEffectKind::Poison)
+                .unwrap()
+                .stacks,
+            5
+        );
+    }
+
+    #[test]
+    fn reapplying_burn_refreshes_its_duration_without_stacking() {
+        let mut app = app();
+        let target = app.world_mut().spawn(StatusEffects::default()).id();
+        apply(&mut app, target, StatusEffectKind::Burn, 2);
+        app.update();
+        apply(&mut app, target, StatusEffectKind::Burn, 2);
+        app.update();
+        let burn = effect(&app, target, StatusEffectKind::Burn).unwrap();
+        assert_eq!(burn.stacks, 1);
+
+        app.update();
+        assert!(effect(&app, target, StatusEffectKind::Burn).is_none());
+    }
+
+    #[test]
+    fn immune_targets_shrug_effects_off() {
+        let mut app = app();
+        let target = app
+            .world_mut()
+            .spawn((
+                StatusEffects::default(),
+                Immunities(vec![StatusEffectKind::Stun]),
+            ))
+            .id();
+        apply(&mut app, target, StatusEffectKind::Stun, 4);
+        apply(&mut app, target, StatusEffectKind::Slow, 4);
+        let status_effects = app.world().get::<StatusEffects>(target).unwrap();
+        assert!(!status_effects.has(StatusEffectKind::Stun));
+        assert!(status_effects.has(StatusEffectKind::Slow));
+    }
+
+    #[test]
+    fn effects_wear_off_after_their_duration() {
+        let mut app = app();
+        let target = app.world_mut().spawn(StatusEffects::default()).id();
+        apply(&mut app, target, StatusEffectKind::Slow, 2);
+        let speed = |app: &App| {
+            app.world()
+                .get::<StatusEffects>(target)
+                .unwrap()
+                .speed_multiplier()
+        };
+        assert!((speed(&app) - 0.5).abs() < f32::EPSILON);
+        app.update();
+        assert!((speed(&app) - 0.5).abs() < f32::EPSILON);
+        app.update();
+        assert!((speed(&app) - 1.).abs() < f32::EPSILON);
+    }
+}